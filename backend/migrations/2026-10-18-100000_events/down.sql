@@ -0,0 +1,2 @@
+ALTER TABLE "archer_additions" DROP COLUMN "event";
+DROP TABLE "events";
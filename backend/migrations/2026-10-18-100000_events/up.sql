@@ -0,0 +1,11 @@
+CREATE TABLE "events" (
+	"id"	INTEGER NOT NULL,
+	"name"	TEXT NOT NULL,
+	"date"	TEXT NOT NULL,
+	"venue"	TEXT NOT NULL,
+	PRIMARY KEY("id")
+);
+-- Registrations from before multi-event support belong to the first tournament
+INSERT INTO "events" SELECT 1, 'Vereinsmeisterschaft WA', '2023-05-21', ''
+	WHERE EXISTS (SELECT * FROM "archer_additions");
+ALTER TABLE "archer_additions" ADD COLUMN "event"	INTEGER NOT NULL DEFAULT 1;
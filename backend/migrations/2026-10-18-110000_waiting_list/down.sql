@@ -0,0 +1 @@
+ALTER TABLE "archer_additions" DROP COLUMN "status";
@@ -0,0 +1 @@
+ALTER TABLE "archer_additions" ADD COLUMN "status"	TEXT NOT NULL DEFAULT 'registered';
@@ -0,0 +1,2 @@
+ALTER TABLE "archer_additions" DROP COLUMN "verification_expires";
+ALTER TABLE "archer_additions" DROP COLUMN "verification_token";
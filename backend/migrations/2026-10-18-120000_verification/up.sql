@@ -0,0 +1,2 @@
+ALTER TABLE "archer_additions" ADD COLUMN "verification_token"	TEXT;
+ALTER TABLE "archer_additions" ADD COLUMN "verification_expires"	TEXT;
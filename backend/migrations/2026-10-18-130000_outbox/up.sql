@@ -0,0 +1,14 @@
+CREATE TABLE "outbox" (
+	"id"	INTEGER NOT NULL,
+	"recipient_name"	TEXT NOT NULL,
+	"recipient"	TEXT NOT NULL,
+	"subject"	TEXT NOT NULL,
+	"template"	TEXT NOT NULL,
+	"data"	TEXT NOT NULL,
+	"status"	TEXT NOT NULL DEFAULT 'queued',
+	"attempts"	INTEGER NOT NULL DEFAULT 0,
+	"next_attempt"	TEXT NOT NULL,
+	"last_error"	TEXT,
+	"created"	TEXT NOT NULL,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
@@ -0,0 +1,2 @@
+-- Organizers only hear about registrations from now on
+ALTER TABLE "archer_additions" ADD COLUMN "organizer_notified"	INTEGER NOT NULL DEFAULT 1;
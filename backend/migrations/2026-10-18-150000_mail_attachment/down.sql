@@ -0,0 +1,3 @@
+ALTER TABLE "outbox" DROP COLUMN "attachment";
+ALTER TABLE "outbox" DROP COLUMN "attachment_type";
+ALTER TABLE "outbox" DROP COLUMN "attachment_name";
@@ -0,0 +1,3 @@
+ALTER TABLE "outbox" ADD COLUMN "attachment_name"	TEXT;
+ALTER TABLE "outbox" ADD COLUMN "attachment_type"	TEXT;
+ALTER TABLE "outbox" ADD COLUMN "attachment"	TEXT;
@@ -0,0 +1 @@
+-- Nothing to undo, the dates stay valid in the new format
@@ -0,0 +1,5 @@
+-- Registrations so far were saved without date of birth. Dates entered in German
+-- format, e.g. in Ianseo, get the format Ianseo imports.
+UPDATE "archers"
+SET "date of birth" = substr("date of birth", 7, 4) || '-' || substr("date of birth", 4, 2) || '-' || substr("date of birth", 1, 2)
+WHERE "date of birth" GLOB '[0-3][0-9].[01][0-9].[12][0-9][0-9][0-9]';
@@ -0,0 +1,18 @@
+UPDATE "archers" SET "class" = 'B10' WHERE "class" = 'B210';
+UPDATE "archers" SET "class" = 'B11' WHERE "class" = 'B211';
+UPDATE "archers" SET "class" = 'B20' WHERE "class" = 'B220';
+UPDATE "archers" SET "class" = 'B30' WHERE "class" = 'B230';
+UPDATE "archers" SET "class" = 'B12' WHERE "class" = 'B120';
+UPDATE "archers" SET "class" = 'C10' WHERE "class" = 'C110';
+UPDATE "archers" SET "class" = 'C11' WHERE "class" = 'C111';
+UPDATE "archers" SET "class" = 'C20' WHERE "class" = 'C120';
+UPDATE "archers" SET "class" = 'C30' WHERE "class" = 'C130';
+UPDATE "archers" SET "class" = 'C40' WHERE "class" = 'C140';
+UPDATE "archers" SET "class" = 'C12' WHERE "class" = 'C112';
+UPDATE "archers" SET "class" = 'C13' WHERE "class" = 'C113';
+UPDATE "archers" SET "class" = 'C14' WHERE "class" = 'C114';
+UPDATE "archers" SET "target" = 'Spot' WHERE "target" = 'spot';
+UPDATE "archers" SET "target" = 'Cm40' WHERE "target" = '40cm';
+UPDATE "archers" SET "target" = 'Cm60' WHERE "target" = '60cm';
+UPDATE "archers" SET "target" = 'Cm80' WHERE "target" = '80cm';
+UPDATE "archers" SET "target" = 'Cm122' WHERE "target" = '122cm';
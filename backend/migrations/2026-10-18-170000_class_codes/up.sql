@@ -0,0 +1,21 @@
+-- Classes and target faces were stored with the names of the Rust enum variants.
+-- Recurve classes and the open class keep their codes.
+UPDATE "archers" SET "class" = 'B210' WHERE "class" = 'B10';
+UPDATE "archers" SET "class" = 'B211' WHERE "class" = 'B11';
+UPDATE "archers" SET "class" = 'B220' WHERE "class" = 'B20';
+UPDATE "archers" SET "class" = 'B230' WHERE "class" = 'B30';
+UPDATE "archers" SET "class" = 'B120' WHERE "class" = 'B12';
+UPDATE "archers" SET "class" = 'C110' WHERE "class" = 'C10';
+UPDATE "archers" SET "class" = 'C111' WHERE "class" = 'C11';
+UPDATE "archers" SET "class" = 'C120' WHERE "class" = 'C20';
+UPDATE "archers" SET "class" = 'C130' WHERE "class" = 'C30';
+UPDATE "archers" SET "class" = 'C140' WHERE "class" = 'C40';
+UPDATE "archers" SET "class" = 'C112' WHERE "class" = 'C12';
+UPDATE "archers" SET "class" = 'C113' WHERE "class" = 'C13';
+UPDATE "archers" SET "class" = 'C114' WHERE "class" = 'C14';
+-- Butt assignments from Ianseo stay as they are
+UPDATE "archers" SET "target" = 'spot' WHERE "target" = 'Spot';
+UPDATE "archers" SET "target" = '40cm' WHERE "target" = 'Cm40';
+UPDATE "archers" SET "target" = '60cm' WHERE "target" = 'Cm60';
+UPDATE "archers" SET "target" = '80cm' WHERE "target" = 'Cm80';
+UPDATE "archers" SET "target" = '122cm' WHERE "target" = 'Cm122';
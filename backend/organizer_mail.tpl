@@ -0,0 +1,16 @@
+Hallo,
+
+{{#if digest}}seit der letzten Übersicht gab es {{count}} neue Anmeldungen.{{else}}es gibt eine neue Anmeldung.{{/if}}
+{{#each classes}}
+
+{{class}}
+{{#each registrations}}
+- {{first_name}} {{last_name}}, {{event_name}}{{#if session}}, {{session}}{{/if}}{{#if waiting_list}} (Warteliste){{/if}}
+  Mail: {{mail}}
+{{#if date_of_birth}}  Geburtsdatum: {{date_of_birth}}
+{{/if}}  Scheibe: {{target_face}}
+  Kommentar: {{comment}}
+{{/each}}
+{{/each}}
+
+Diese Mail wurde automatisch vom Anmeldesystem verschickt.
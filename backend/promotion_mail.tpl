@@ -0,0 +1,9 @@
+Hallo {{first_name}},
+
+gute Nachrichten: Für die {{event_name}} am {{event_date}} in {{event_venue}} ist ein Platz frei geworden.
+Du bist von der Warteliste nachgerückt und damit fest angemeldet.
+{{#if session}}
+Durchgang: {{session}}
+{{/if}}
+Viele Grüße und Alle ins Gold
+Sportleitung der Bogenabteilung des PSV München
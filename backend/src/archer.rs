@@ -1,125 +1,684 @@
-use crate::{error::*, schema, CONFIG, HANDLEBARS};
-use axum::{http::StatusCode, response::IntoResponse, Json};
-use common::archer::Archer;
-use common::class::Class;
+use crate::{
+    auth::Editor,
+    config::EventConfig,
+    error::*,
+    event,
+    models::{RegisteredArcher, Status},
+    organizer, outbox, registration,
+    repository::{self, EntryFilter, Page, SortBy},
+    verification, CONFIG,
+};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{Local, NaiveDateTime, Utc};
+use common::archer::{Archer, Registration};
+use common::{bow_type::BowType, class::Class};
 use diesel::prelude::*;
-use lettre::message::Mailbox;
-use lettre::transport::smtp::authentication::Credentials;
-use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
-use log::warn;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
+/// Default number of archers returned per page by [`list_archers`]
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 200;
+
 #[axum::debug_handler]
-pub async fn create_archer(Json(payload): Json<Archer>) -> Result<impl IntoResponse> {
+pub async fn create_archer(
+    Path(event_id): Path<i32>,
+    Json(payload): Json<Archer>,
+) -> Result<impl IntoResponse> {
     println!("Received {} {}", payload.first_name, payload.last_name);
-
-    let email_data = BTreeMap::from([
-        ("first_name", payload.first_name.clone()),
-        ("last_name", payload.last_name.clone()),
-        (
-            "date_of_birth",
-            payload.date_of_birth().format("%d.%m.%Y").to_string(),
-        ),
-        ("class", payload.class().name().to_owned()),
-        ("target_face", payload.target_face().to_string()),
-        ("comment", payload.comment.clone()),
-    ]);
+    let event = event::find_event(event_id)?;
+    event::check_registration_window(&event)?;
+    payload.validate(&event.info())?;
 
     let archer = payload.clone();
-    let save_task = tokio::task::spawn_blocking(move || save_archer(archer));
-    let (save, mail) = tokio::join!(save_task, send_registration_mail(&payload, email_data));
+    tokio::task::spawn_blocking(move || {
+        save_archer(&mut crate::db::establish_connection(), archer, &event)
+    })
+    .await
+    .unwrap()?;
+    outbox::wake();
+
+    Ok((StatusCode::ACCEPTED, Json(payload)))
+}
+
+/// Name and start time of the session for the mails, empty for events without sessions
+pub fn session_description(event: &EventConfig, session: i32) -> String {
+    event
+        .sessions
+        .iter()
+        .find(|s| s.id == session)
+        .map(|s| format!("{} ({} Uhr)", s.name, s.start.format("%H:%M")))
+        .unwrap_or_default()
+}
+
+/// Query parameters accepted by [`list_archers`]
+#[derive(Deserialize, Debug)]
+pub struct ListQuery {
+    event: Option<i32>,
+    class: Option<String>,
+    division: Option<String>,
+    session: Option<i32>,
+    target: Option<String>,
+    /// `registered` or `waiting`
+    status: Option<String>,
+    #[serde(default)]
+    sort: SortBy,
+    /// 1-based page number
+    page: Option<u32>,
+    /// Capped at [`MAX_PAGE_SIZE`]
+    per_page: Option<u32>,
+}
+
+pub async fn list_archers(Query(query): Query<ListQuery>) -> Result<impl IntoResponse> {
+    let archers = tokio::task::spawn_blocking(move || load_archers(query))
+        .await
+        .unwrap()?;
+    Ok(Json(archers))
+}
+
+/// Entry of the admin archer list
+#[derive(Serialize)]
+pub struct ListedArcher {
+    #[serde(flatten)]
+    archer: RegisteredArcher,
+    /// 1-based position on the waiting list of the archer's event and session
+    waitlist_position: Option<usize>,
+}
+
+fn load_archers(query: ListQuery) -> Result<Vec<ListedArcher>> {
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let page = query.page.unwrap_or(1).max(1);
+    let offset = i64::from(page - 1)
+        .checked_mul(per_page.into())
+        .ok_or(Error::InvalidPage)?;
+    let filter = EntryFilter {
+        event: query.event,
+        class: query.class,
+        division: query.division,
+        session: query.session,
+        target: query.target,
+        status: query.status,
+        without_pending: false,
+    };
+    let page = Page {
+        limit: per_page.into(),
+        offset,
+    };
+
+    let mut connection = crate::db::establish_connection();
+    let archers = repository::list_entries(&mut connection, filter, query.sort, Some(page))?;
+    let positions = repository::waitlist_positions(&mut connection)?;
+    Ok(archers
+        .into_iter()
+        .map(|archer| ListedArcher {
+            waitlist_position: positions.get(&archer.bib).copied(),
+            archer,
+        })
+        .collect())
+}
+
+pub async fn list_event_archers(
+    Path(event): Path<i32>,
+    Query(mut query): Query<ListQuery>,
+) -> Result<impl IntoResponse> {
+    query.event = Some(event);
+    list_archers(Query(query)).await
+}
+
+pub async fn delete_archer(Editor(admin): Editor, Path(bib): Path<i32>) -> Result<StatusCode> {
+    tokio::task::spawn_blocking(move || {
+        crate::db::establish_connection().immediate_transaction(|conn| remove_archer(conn, bib))
+    })
+    .await
+    .unwrap()?;
+    outbox::wake();
+    log::info!("Admin {} deleted archer {}", admin.username, bib);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Deletes the archer and promotes the next one from the waiting list into the free place
+pub fn remove_archer(conn: &mut SqliteConnection, bib: i32) -> Result<()> {
+    let registration = match repository::load(conn, bib) {
+        Ok(registration) => Some(registration),
+        // Archers entered in Ianseo have no registration but can be deleted as well
+        Err(Error::NotFound) => None,
+        Err(e) => return Err(e),
+    };
+    repository::delete(conn, bib)?;
+
+    match registration {
+        Some(registration) if registration.status == Status::Registered => {
+            match event::find_event(registration.event) {
+                Ok(event) => promote_next(conn, &event),
+                // Events removed from the config don't take registrations anymore
+                Err(_) => Ok(()),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Moves the first waiting archer whose session has room up to the registered archers
+fn promote_next(conn: &mut SqliteConnection, event: &EventConfig) -> Result<()> {
+    let waiting = repository::list(conn, Some(event.id))?
+        .into_iter()
+        .filter(|archer| archer.status == Status::Waiting);
+    for archer in waiting {
+        match check_capacity(conn, event, archer.session) {
+            Ok(()) => {
+                repository::set_status(conn, archer.bib, Status::Registered)?;
+                log::info!("Promoted archer {} from the waiting list", archer.bib);
+                return queue_promotion_mail(
+                    conn,
+                    event,
+                    archer.bib,
+                    archer.session,
+                    &archer.first_name,
+                    &archer.last_name,
+                    &archer.mail,
+                );
+            }
+            Err(Error::SessionFull) => continue,
+            Err(Error::EventFull) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Fails with [`Error::EventFull`] or [`Error::SessionFull`] if there is no room for another archer
+fn check_capacity(conn: &mut SqliteConnection, event: &EventConfig, session: i32) -> Result<()> {
+    if let Some(capacity) = event.capacity {
+        let registered = repository::count_registered(conn, event.id, None)?;
+        if registered >= capacity.into() {
+            return Err(Error::EventFull);
+        }
+    }
+    check_session_capacity(conn, event, session)
+}
+
+/// Fails with [`Error::SessionFull`] if there is no room for another archer in the session
+pub fn check_session_capacity(
+    conn: &mut SqliteConnection,
+    event: &EventConfig,
+    session: i32,
+) -> Result<()> {
+    let session_capacity = event
+        .sessions
+        .iter()
+        .find(|s| s.id == session)
+        .and_then(|s| s.capacity());
+    if let Some(capacity) = session_capacity {
+        let registered = repository::count_registered(conn, event.id, Some(session))?;
+        if registered >= capacity.into() {
+            return Err(Error::SessionFull);
+        }
+    }
+    Ok(())
+}
+
+/// Ianseo division of the class
+pub fn division(cls: &Class) -> &'static str {
+    match cls.bow_type() {
+        BowType::Recurve => "R",
+        BowType::Barebow => "B",
+        BowType::Compound => "C",
+    }
+}
 
-    save.unwrap()?;
-    mail?;
+/// Stores the archer as [`Status::Pending`] until the email address is confirmed
+/// and queues the mail asking for the confirmation
+pub fn save_archer(
+    conn: &mut SqliteConnection,
+    archer: Archer,
+    event: &EventConfig,
+) -> Result<i32> {
+    let token = verification::new_token();
+    let expires = verification::expiry_time();
+    conn.immediate_transaction(|conn| -> Result<i32> {
+        if event.reject_when_full {
+            // Saves the archer a pointless confirmation. The place is only taken by `confirm_archer`.
+            check_capacity(conn, event, archer.session())?;
+        }
 
-    Ok((StatusCode::CREATED, Json(payload)))
+        let inserted_bib = repository::insert(conn, &archer, event.id, &token, expires)?;
+        verification::queue_verification_mail(conn, event, &archer, &token, expires)?;
+
+        Ok(inserted_bib)
+    })
 }
 
-pub async fn list_archers() -> impl IntoResponse {
-    (StatusCode::NOT_IMPLEMENTED, "501 Not implemented!")
+/// Confirms the email address of a registration. It gets a place or goes on the waiting list
+/// only now and gets the confirmation mail. Confirming a registration a second time changes nothing.
+pub fn confirm_archer(token: &str) -> Result<i32> {
+    let mut connection = crate::db::establish_connection();
+    connection.immediate_transaction(|conn| -> Result<i32> {
+        let (bib, expires) =
+            repository::find_verification(conn, token)?.ok_or(Error::InvalidLink)?;
+        let stored = repository::load(conn, bib)?;
+        if stored.status != Status::Pending {
+            return Ok(bib);
+        }
+        if expires.is_some_and(|expires| expires < Local::now().naive_local()) {
+            return Err(Error::VerificationExpired);
+        }
+
+        let event = event::find_event(stored.event)?;
+        let status = match check_capacity(conn, &event, stored.session) {
+            Ok(()) => Status::Registered,
+            Err(Error::EventFull | Error::SessionFull) if !event.reject_when_full => {
+                Status::Waiting
+            }
+            Err(e) => return Err(e),
+        };
+        repository::set_status(conn, bib, status)?;
+        log::info!("Archer {} confirmed the email address", bib);
+
+        let registration: Registration = repository::load(conn, bib)?.into();
+        queue_registration_mail(conn, &event, &registration, bib)?;
+        organizer::registration_confirmed(conn, bib)?;
+        Ok(bib)
+    })
 }
 
-fn save_archer(archer: Archer) -> Result<()> {
+/// Changes a stored registration. The archer keeps the place unless moving to a full session.
+/// The place left in the old session goes to the next archer on the waiting list.
+/// Queues a new confirmation with the changed data.
+pub fn update_archer(bib: i32, archer: Archer, event: &EventConfig) -> Result<Registration> {
     let mut connection = crate::db::establish_connection();
-    connection.transaction(|conn| -> Result<()> {
-        let inserted_bib: i32 = diesel::insert_into(schema::archers::table)
-            .values(crate::models::InsertableArcher {
-                session: 1,
-                division: match archer.class() {
-                    c if Class::recurve_classes().contains(&c) => "R".to_string(),
-                    c if Class::barebow_classes().contains(&c) => "B".to_string(),
-                    c if Class::compound_classes().contains(&c) => "C".to_string(),
-                    _ => unreachable!(),
-                },
-                class: format!("{:?}", archer.class()),
-                target: format!("{:?}", archer.target_face()),
-                individual_qualification: 1,
-                team_qualification: 1,
-                individual_final: 1,
-                team_final: 1,
-                mixed_team_final: 1,
-                last_name: archer.last_name.clone(),
-                first_name: archer.first_name.clone(),
-                gender: None,
-                country_code: "PSV".to_string(),
-                country_name: "PSV München".to_string(),
-                ..Default::default()
-            })
-            .returning(schema::archers::bib)
-            .get_result(conn)?;
-
-        diesel::insert_into(schema::archer_additions::table)
-            .values(crate::models::ArcherAdditions {
-                bib: inserted_bib,
-                email: archer.mail.as_str().to_owned(),
-                comment: archer.comment,
-            })
-            .execute(conn)?;
-
-        Ok(())
+    connection.immediate_transaction(|conn| -> Result<Registration> {
+        let stored = repository::load(conn, bib)?;
+        let changes_place =
+            stored.session != archer.session() && stored.status == Status::Registered;
+        if changes_place {
+            check_session_capacity(conn, event, archer.session())?;
+        }
+
+        repository::update(conn, bib, &archer)?;
+
+        if changes_place {
+            promote_next(conn, event)?;
+        }
+
+        let registration: Registration = repository::load(conn, bib)?.into();
+        queue_registration_mail(conn, event, &registration, bib)?;
+        Ok(registration)
     })
 }
 
-async fn send_registration_mail(
-    archer: &Archer,
-    email_data: BTreeMap<&'static str, String>,
+/// Queues the confirmation with the current data of the registration and a link to change it
+fn queue_registration_mail(
+    conn: &mut SqliteConnection,
+    event: &EventConfig,
+    registration: &Registration,
+    bib: i32,
 ) -> Result<()> {
-    let credentials = Credentials::new(
-        CONFIG.read().mail_server.smtp_username.clone(),
-        CONFIG.read().mail_server.smtp_password.clone(),
+    let email_data = registration_mail_data(event, registration, bib);
+    let subject = CONFIG.read().mail_message.subject.clone();
+    // Archers on the waiting list don't know yet whether they can come
+    let calendar = (!registration.waiting_list)
+        .then(|| calendar_attachment(event, registration.session, bib, false));
+    outbox::enqueue_with_attachment(
+        conn,
+        format!("{} {}", registration.first_name, registration.last_name),
+        &registration.mail,
+        &subject,
+        &event::mail_template_name(event),
+        &email_data,
+        calendar,
+    )
+}
+
+/// Data for the registration mail template of the event
+pub fn registration_mail_data(
+    event: &EventConfig,
+    registration: &Registration,
+    bib: i32,
+) -> BTreeMap<&'static str, String> {
+    // Handlebars treats the empty string as false
+    BTreeMap::from([
+        ("event_name", event.name.clone()),
+        ("event_date", event.date.format("%d.%m.%Y").to_string()),
+        ("event_venue", event.venue.clone()),
+        ("first_name", registration.first_name.clone()),
+        ("last_name", registration.last_name.clone()),
+        (
+            "date_of_birth",
+            registration
+                .date_of_birth
+                .map(|dob| dob.format("%d.%m.%Y").to_string())
+                .unwrap_or_default(),
+        ),
+        (
+            "gender",
+            registration
+                .gender
+                .map(|gender| gender.name().to_owned())
+                .unwrap_or_default(),
+        ),
+        (
+            "class",
+            registration
+                .class
+                .as_ref()
+                .map(|cls| cls.name())
+                .unwrap_or_default(),
+        ),
+        (
+            "target_face",
+            registration
+                .target_face
+                .map(|tf| tf.to_string())
+                .unwrap_or_default(),
+        ),
+        ("session", session_description(event, registration.session)),
+        ("comment", registration.comment.clone()),
+        (
+            "waiting_list",
+            if registration.waiting_list {
+                "true"
+            } else {
+                ""
+            }
+            .to_string(),
+        ),
+        ("manage_url", registration::manage_url(bib)),
+    ])
+}
+
+/// iCalendar file with the event for the mail, starting with the session if the event has
+/// sessions. A cancelled entry removes the one sent before from the calendar.
+pub fn calendar_attachment(
+    event: &EventConfig,
+    session: i32,
+    bib: i32,
+    cancelled: bool,
+) -> outbox::MailAttachment {
+    let (filename, method) = if cancelled {
+        ("abmeldung.ics", "CANCEL")
+    } else {
+        ("anmeldung.ics", "PUBLISH")
+    };
+    outbox::MailAttachment {
+        filename: filename.to_string(),
+        content_type: format!("text/calendar; charset=utf-8; method={method}"),
+        content: calendar_event(event, session, bib, Utc::now().naive_utc(), cancelled),
+    }
+}
+
+/// All versions of the entry of a registration share the UID. Calendars replace an entry
+/// by one with a higher SEQUENCE, so it is the time of the change.
+fn calendar_event(
+    event: &EventConfig,
+    session: i32,
+    bib: i32,
+    now: NaiveDateTime,
+    cancelled: bool,
+) -> String {
+    let host = CONFIG
+        .read()
+        .public_url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/')
+        .to_string();
+    let session = event.sessions.iter().find(|s| s.id == session);
+    // Local times without time zone, calendars show them as they are
+    let (start, end) = match session {
+        Some(session) => (
+            format!(
+                "DTSTART:{}",
+                event.date.and_time(session.start).format("%Y%m%dT%H%M%S")
+            ),
+            None,
+        ),
+        None => (
+            format!("DTSTART;VALUE=DATE:{}", event.date.format("%Y%m%d")),
+            Some(format!(
+                "DTEND;VALUE=DATE:{}",
+                (event.date + chrono::Duration::days(1)).format("%Y%m%d")
+            )),
+        ),
+    };
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//PSV München//Anmeldung//DE".to_string(),
+        format!("METHOD:{}", if cancelled { "CANCEL" } else { "PUBLISH" }),
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:registration-{}-{}@{}", event.id, bib, host),
+        format!("SEQUENCE:{}", now.timestamp()),
+        format!("DTSTAMP:{}", now.format("%Y%m%dT%H%M%SZ")),
+        start,
+    ];
+    lines.extend(end);
+    lines.push(format!("SUMMARY:{}", ical_text(&event.name)));
+    if cancelled {
+        lines.push("STATUS:CANCELLED".to_string());
+    }
+    if !event.venue.is_empty() {
+        lines.push(format!("LOCATION:{}", ical_text(&event.venue)));
+    }
+    if let Some(session) = session {
+        lines.push(format!(
+            "DESCRIPTION:{}",
+            ical_text(&format!("Durchgang: {}", session.name))
+        ));
+    }
+    lines.push("END:VEVENT".to_string());
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| ical_fold(line) + "\r\n").collect()
+}
+
+/// Escapes a text value for iCalendar. Carriage returns are dropped, they would end the line.
+fn ical_text(text: &str) -> String {
+    text.replace('\r', "")
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Splits lines longer than 75 bytes as required by iCalendar
+fn ical_fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+fn queue_promotion_mail(
+    conn: &mut SqliteConnection,
+    event: &EventConfig,
+    bib: i32,
+    session: i32,
+    first_name: &str,
+    last_name: &str,
+    address: &str,
+) -> Result<()> {
+    let email_data = promotion_mail_data(event, session, first_name, last_name);
+    let subject = {
+        let config = CONFIG.read();
+        config
+            .mail_message
+            .promotion_subject
+            .clone()
+            .unwrap_or_else(|| config.mail_message.subject.clone())
+    };
+    outbox::enqueue_with_attachment(
+        conn,
+        format!("{} {}", first_name, last_name),
+        address,
+        &subject,
+        "promotion_mail",
+        &email_data,
+        Some(calendar_attachment(event, session, bib, false)),
+    )
+}
+
+pub fn promotion_mail_data(
+    event: &EventConfig,
+    session: i32,
+    first_name: &str,
+    last_name: &str,
+) -> BTreeMap<&'static str, String> {
+    BTreeMap::from([
+        ("event_name", event.name.clone()),
+        ("event_date", event.date.format("%d.%m.%Y").to_string()),
+        ("event_venue", event.venue.clone()),
+        ("first_name", first_name.to_string()),
+        ("last_name", last_name.to_string()),
+        ("session", session_description(event, session)),
+    ])
+}
+
+#[test]
+fn test_calendar_event() {
+    let event: EventConfig = toml::from_str(
+        r#"
+        id = 1
+        name = "Vereinsmeisterschaft WA"
+        date = "2023-05-21"
+        venue = "Bogensportplatz, München"
+        season_start = "2023-01-01"
+        sessions = [{ id = 2, name = "Nachmittag", start = "14:00:00" }]
+        "#,
+    )
+    .unwrap();
+    let now = NaiveDateTime::parse_from_str("2023-04-01 12:00:00", crate::models::TIMESTAMP_FORMAT)
+        .unwrap();
+
+    let calendar = calendar_event(&event, 2, 7, now, false);
+    assert!(calendar.contains("\r\nMETHOD:PUBLISH\r\n"));
+    assert!(calendar.contains("\r\nSEQUENCE:1680350400\r\n"));
+    assert!(calendar.contains("\r\nDTSTAMP:20230401T120000Z\r\nDTSTART:20230521T140000\r\n"));
+    assert!(calendar.contains("\r\nLOCATION:Bogensportplatz\\, München\r\n"));
+    assert!(calendar.contains("\r\nDESCRIPTION:Durchgang: Nachmittag\r\n"));
+    // Without session the whole day
+    let calendar = calendar_event(&event, 1, 7, now, false);
+    assert!(calendar.contains("\r\nDTSTART;VALUE=DATE:20230521\r\nDTEND;VALUE=DATE:20230522\r\n"));
+    let calendar = calendar_event(&event, 2, 7, now, true);
+    assert!(calendar.contains("\r\nMETHOD:CANCEL\r\n"));
+    assert!(calendar.contains("\r\nSTATUS:CANCELLED\r\n"));
+    assert_eq!(ical_text("Halle 1\r\nTor B\r"), "Halle 1\\nTor B");
+
+    let long = "x".repeat(100);
+    assert_eq!(
+        ical_fold(&long),
+        format!("{}\r\n {}", &long[..75], &long[75..])
+    );
+}
+
+#[test]
+fn test_session_capacity() {
+    let mut conn = crate::db::test_connection();
+    let event = crate::config::test_event(
+        r#"
+        reject_when_full = true
+        sessions = [
+            { id = 1, name = "Vormittag", start = "09:00:00", max_archers = 1 },
+            { id = 2, name = "Nachmittag", start = "14:00:00" },
+        ]
+        "#,
     );
-    let email = Message::builder()
-        .from(Mailbox::new(
-            Some(CONFIG.read().mail_message.sender_name.clone()),
-            CONFIG
-                .read()
-                .mail_message
-                .sender_address
-                .as_str()
-                .parse()
-                .map_err(|e| {
-                    warn!("received wrong email format: {:?}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        "Falsches Email Format!".to_string(),
-                    )
-                })
-                .unwrap(),
-        ))
-        .to(Mailbox::new(
-            Some(format!("{} {}", archer.first_name, archer.last_name)),
-            archer.mail.as_str().parse().unwrap(),
-        ))
-        .header(lettre::message::header::ContentType::TEXT_PLAIN)
-        .subject(&CONFIG.read().mail_message.subject)
-        .body(HANDLEBARS.read().render("user_mail", &email_data).unwrap())
+    let save = |conn: &mut SqliteConnection, mail, session| {
+        save_archer(conn, repository::test_archer(mail, session), &event)
+    };
+
+    let first = save(&mut conn, "robin@example.com", 1).ok().unwrap();
+    // Unconfirmed registrations don't take the place
+    save(&mut conn, "marian@example.com", 1).ok().unwrap();
+    repository::set_status(&mut conn, first, Status::Registered)
+        .ok()
         .unwrap();
+    assert!(matches!(
+        save(&mut conn, "john@example.com", 1),
+        Err(Error::SessionFull)
+    ));
+    save(&mut conn, "john@example.com", 2).ok().unwrap();
 
-    let mailer: AsyncSmtpTransport<Tokio1Executor> =
-        AsyncSmtpTransport::<Tokio1Executor>::relay(&CONFIG.read().mail_server.smtp_server)
-            .unwrap()
-            .credentials(credentials)
-            .build();
-    mailer.send(email).await?;
-    Ok(())
+    // The rejected registration was rolled back
+    let mails: Vec<String> = repository::list(&mut conn, Some(event.id))
+        .ok()
+        .unwrap()
+        .into_iter()
+        .map(|archer| archer.mail)
+        .collect();
+    assert_eq!(
+        mails,
+        [
+            "robin@example.com",
+            "marian@example.com",
+            "john@example.com"
+        ]
+    );
+    assert_eq!(
+        repository::count_registered(&mut conn, event.id, Some(1))
+            .ok()
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        repository::count_registered(&mut conn, event.id, Some(2))
+            .ok()
+            .unwrap(),
+        0
+    );
+}
+
+#[test]
+fn test_promotion() {
+    use crate::{models::OutboxMail, schema::outbox};
+
+    let mut conn = crate::db::test_connection();
+    let event = crate::config::test_event("capacity = 1");
+    let mut save = |mail, status| {
+        let bib = save_archer(&mut conn, repository::test_archer(mail, 1), &event)
+            .ok()
+            .unwrap();
+        repository::set_status(&mut conn, bib, status).ok().unwrap();
+        bib
+    };
+    let robin = save("robin@example.com", Status::Registered);
+    let marian = save("marian@example.com", Status::Waiting);
+    let john = save("john@example.com", Status::Waiting);
+    let positions = repository::waitlist_positions(&mut conn).ok().unwrap();
+    assert_eq!(positions.get(&marian), Some(&1));
+    assert_eq!(positions.get(&john), Some(&2));
+
+    repository::delete(&mut conn, robin).ok().unwrap();
+    promote_next(&mut conn, &event).ok().unwrap();
+    let status =
+        |conn: &mut SqliteConnection, bib| repository::load(conn, bib).ok().unwrap().status;
+    assert_eq!(status(&mut conn, marian), Status::Registered);
+    assert_eq!(status(&mut conn, john), Status::Waiting);
+    let positions = repository::waitlist_positions(&mut conn).ok().unwrap();
+    assert_eq!(positions.get(&john), Some(&1));
+
+    let mails: Vec<OutboxMail> = outbox::table
+        .filter(outbox::template.eq("promotion_mail"))
+        .load(&mut conn)
+        .unwrap();
+    assert_eq!(mails.len(), 1);
+    assert_eq!(mails[0].recipient, "marian@example.com");
+    assert_eq!(mails[0].attachment_name.as_deref(), Some("anmeldung.ics"));
+
+    // The event is full again
+    promote_next(&mut conn, &event).ok().unwrap();
+    assert_eq!(status(&mut conn, john), Status::Waiting);
 }
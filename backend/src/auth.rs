@@ -0,0 +1,259 @@
+use crate::{error::*, CONFIG};
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use lazy_static::lazy_static;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How long an admin stays logged in after the login
+const SESSION_DURATION: Duration = Duration::from_secs(12 * 60 * 60);
+/// Failed logins of a username from an address before further attempts have to wait
+const FREE_LOGIN_ATTEMPTS: u32 = 3;
+const MAX_LOGIN_DELAY: Duration = Duration::from_secs(5 * 60);
+/// Failed logins are forgotten after this time without another one
+const LOGIN_FAILURE_MEMORY: Duration = Duration::from_secs(60 * 60);
+
+lazy_static! {
+    static ref SESSIONS: Mutex<HashMap<String, AdminSession>> = Mutex::new(HashMap::new());
+    /// Counted per address and username, so others can't lock an admin out
+    static ref LOGIN_FAILURES: Mutex<HashMap<LoginKey, LoginFailures>> = Mutex::new(HashMap::new());
+    /// Checked for unknown usernames, so they take as long as wrong passwords
+    static ref DUMMY_HASH: String = hash_password(&random_token());
+}
+
+type LoginKey = (IpAddr, String);
+
+struct LoginFailures {
+    count: u32,
+    last: Instant,
+}
+
+/// Permissions of an admin. An editor may do everything a reader may do.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Reader,
+    Editor,
+}
+
+#[derive(Clone, Debug)]
+pub struct AdminSession {
+    pub username: String,
+    pub role: Role,
+    token: String,
+    expires: Instant,
+}
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+#[derive(Serialize)]
+pub struct LoginResponse {
+    token: String,
+    role: Role,
+}
+
+pub async fn login(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<LoginRequest>,
+) -> Result<impl IntoResponse> {
+    let key = (client_address(peer, &headers), payload.username.clone());
+    check_login_delay(&key)?;
+    let admin = CONFIG
+        .read()
+        .admins
+        .iter()
+        .find(|admin| admin.username == payload.username)
+        .map(|admin| (admin.password_hash.clone(), admin.role));
+    let password_hash = admin
+        .as_ref()
+        .map_or_else(|| DUMMY_HASH.clone(), |(hash, _)| hash.clone());
+    let password = payload.password;
+    let valid = tokio::task::spawn_blocking(move || verify_password(&password, &password_hash))
+        .await
+        .unwrap();
+    let Some(role) = admin.filter(|_| valid).map(|(_, role)| role) else {
+        record_login_failure(key);
+        return Err(Error::InvalidCredentials);
+    };
+    LOGIN_FAILURES.lock().unwrap().remove(&key);
+
+    let token = random_token();
+    log::info!("Admin {} logged in", payload.username);
+    SESSIONS.lock().unwrap().insert(
+        token.clone(),
+        AdminSession {
+            username: payload.username,
+            role,
+            token: token.clone(),
+            expires: Instant::now() + SESSION_DURATION,
+        },
+    );
+
+    Ok(Json(LoginResponse { token, role }))
+}
+
+/// Address of the client. Behind a reverse proxy on the same host, like the nginx of the
+/// NixOS module, the address the proxy passes in `X-Real-IP`.
+fn client_address(peer: SocketAddr, headers: &HeaderMap) -> IpAddr {
+    if !peer.ip().is_loopback() {
+        return peer.ip();
+    }
+    headers
+        .get("x-real-ip")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(peer.ip())
+}
+
+/// Fails with [`Error::TooManyLoginAttempts`] until the delay after the last failed login passed
+fn check_login_delay(key: &LoginKey) -> Result<()> {
+    if let Some(failures) = LOGIN_FAILURES.lock().unwrap().get(key) {
+        let wait =
+            (failures.last + login_delay(failures.count)).saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            return Err(Error::TooManyLoginAttempts(wait));
+        }
+    }
+    Ok(())
+}
+
+fn record_login_failure(key: LoginKey) {
+    let now = Instant::now();
+    let mut failures = LOGIN_FAILURES.lock().unwrap();
+    failures.retain(|_, failures| now - failures.last < LOGIN_FAILURE_MEMORY);
+    let (address, username) = &key;
+    log::warn!("Failed login of {} from {}", username, address);
+    let entry = failures.entry(key).or_insert(LoginFailures {
+        count: 0,
+        last: now,
+    });
+    entry.count += 1;
+    entry.last = now;
+}
+
+/// Time to wait after the given number of failed logins, doubled with every further one
+fn login_delay(failures: u32) -> Duration {
+    match failures.checked_sub(FREE_LOGIN_ATTEMPTS) {
+        None => Duration::ZERO,
+        Some(extra) => Duration::from_secs(1 << extra.min(16)).min(MAX_LOGIN_DELAY),
+    }
+}
+
+pub async fn logout(Extension(session): Extension<AdminSession>) -> impl IntoResponse {
+    SESSIONS.lock().unwrap().remove(&session.token);
+    axum::http::StatusCode::NO_CONTENT
+}
+
+/// Middleware rejecting all requests without a valid `Authorization: Bearer <token>` header.
+/// The [`AdminSession`] of the token is added to the request extensions.
+pub async fn require_login<B>(mut req: Request<B>, next: Next<B>) -> Result<Response> {
+    let token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(Error::Unauthorized)?;
+
+    let session = {
+        let mut sessions = SESSIONS.lock().unwrap();
+        let now = Instant::now();
+        sessions.retain(|_, session| session.expires > now);
+        sessions.get(token).cloned().ok_or(Error::Unauthorized)?
+    };
+
+    req.extensions_mut().insert(session);
+    Ok(next.run(req).await)
+}
+
+/// Extractor for handlers that modify data. Requires [`require_login`] to run first.
+pub struct Editor(pub AdminSession);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Editor {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        let session = parts
+            .extensions
+            .get::<AdminSession>()
+            .cloned()
+            .ok_or(Error::Unauthorized)?;
+        if session.role < Role::Editor {
+            return Err(Error::Forbidden);
+        }
+        Ok(Editor(session))
+    }
+}
+
+/// 32 random bytes as hex string
+pub fn random_token() -> String {
+    rand::thread_rng()
+        .gen::<[u8; 32]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("Couldn't hash password")
+        .to_string()
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(e) => {
+            log::error!("Invalid password hash in config: {}", e);
+            false
+        }
+    }
+}
+
+#[test]
+fn test_login_delay() {
+    assert_eq!(login_delay(1), Duration::ZERO);
+    assert_eq!(login_delay(3), Duration::from_secs(1));
+    assert_eq!(login_delay(5), Duration::from_secs(4));
+    assert_eq!(login_delay(100), MAX_LOGIN_DELAY);
+}
+
+#[test]
+fn test_client_address() {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-real-ip", "203.0.113.7".parse().unwrap());
+    let proxy = SocketAddr::from(([127, 0, 0, 1], 40000));
+    let client = SocketAddr::from(([198, 51, 100, 1], 40000));
+    assert_eq!(
+        client_address(proxy, &headers),
+        IpAddr::from([203, 0, 113, 7])
+    );
+    // Only a local proxy is trusted
+    assert_eq!(client_address(client, &headers), client.ip());
+    assert_eq!(client_address(proxy, &HeaderMap::new()), proxy.ip());
+}
@@ -0,0 +1,207 @@
+//! Dry run of the startup for `backend check-config`. Reports all problems with the config,
+//! the mail templates, the mail server and the database at once instead of failing on the
+//! first one or, worse, on the first registration.
+
+use crate::{
+    archer, config::EventConfig, event, load_class_rules, load_config, organizer, outbox,
+    register_templates, registration, repository, transport::Mailer, verification, CliArgs, CONFIG,
+    HANDLEBARS, MIGRATIONS,
+};
+use chrono::{Duration, Local, NaiveDate};
+use common::{archer::Registration, class::ClassRules, gender::Gender};
+use diesel::prelude::*;
+use diesel_migrations::MigrationHarness;
+use serde::Serialize;
+
+/// Prints the problems found. Returns whether there were none.
+pub async fn check_config(args: &CliArgs) -> bool {
+    let config = match load_config(args) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("ERROR {}", e);
+            return false;
+        }
+    };
+    let mut warnings = Vec::new();
+    if config.public_url.is_empty() {
+        warnings.push("No public_url configured, the links in the mails won't work".to_string());
+    }
+    if config.link_secret.is_empty() {
+        warnings.push(
+            "No link_secret configured, links in mails become invalid on restart".to_string(),
+        );
+    }
+    if config.events.is_empty() {
+        warnings.push("No events configured".to_string());
+    }
+    if let Some(html_template) = &args.mail_html_template_file {
+        if !config.events.is_empty()
+            && config
+                .events
+                .iter()
+                .all(|event| event.mail_html_template.is_some())
+        {
+            warnings.push(format!(
+                "HTML template {:?} is never used, all events have their own",
+                html_template
+            ));
+        }
+    }
+
+    let mut problems = register_templates(args, &config);
+    match load_class_rules(&config) {
+        Ok(rules) => ClassRules::set_current(rules),
+        Err(e) => problems.push(e),
+    }
+    let mailer = Mailer::new(&config);
+    let logo = config.mail_message.logo.is_some();
+    *CONFIG.write() = config;
+    problems.extend(render_templates(logo));
+    match mailer {
+        Ok(mailer) => {
+            if let Err(e) = mailer.test_connection().await {
+                problems.push(e.to_string());
+            }
+        }
+        Err(e) => problems.push(e.to_string()),
+    }
+    match tokio::task::spawn_blocking(check_database).await.unwrap() {
+        Ok((pending, outdated)) => {
+            if pending > 0 {
+                println!("{} database migrations will run on the next start", pending);
+            }
+            warnings.extend(outdated);
+        }
+        Err(e) => problems.push(e),
+    }
+
+    for warning in &warnings {
+        println!("WARNING {}", warning);
+    }
+    for problem in &problems {
+        println!("ERROR {}", problem);
+    }
+    if problems.is_empty() {
+        println!("Config is ok");
+    }
+    problems.is_empty()
+}
+
+/// Renders all templates in strict mode with sample data for each event
+fn render_templates(logo: bool) -> Vec<String> {
+    let events = CONFIG.read().events.clone();
+    let mut problems = Vec::new();
+    let registrations: Vec<Registration> = events.iter().map(sample_registration).collect();
+    for (event, registration) in events.iter().zip(&registrations) {
+        let data = archer::registration_mail_data(event, registration, 1);
+        problems.extend(render(
+            &event::mail_template_name(event),
+            &data,
+            logo,
+            event,
+        ));
+    }
+    // The other templates are the same for all events
+    if let (Some(event), Some(registration)) = (events.first(), registrations.first()) {
+        let expires = Local::now().naive_local() + Duration::hours(48);
+        let data = verification::verification_mail_data(
+            event,
+            &registration.first_name,
+            &registration.last_name,
+            "token",
+            expires,
+        );
+        problems.extend(render("verification_mail", &data, logo, event));
+        let data = archer::promotion_mail_data(
+            event,
+            registration.session,
+            &registration.first_name,
+            &registration.last_name,
+        );
+        problems.extend(render("promotion_mail", &data, logo, event));
+        let data = registration::withdrawal_mail_data(event, registration);
+        problems.extend(render("withdrawal_mail", &data, logo, event));
+        let data = organizer::organizer_mail_data(true, registrations.clone());
+        problems.extend(render("organizer_mail", &data, logo, event));
+    }
+    problems
+}
+
+fn render(
+    template: &str,
+    email_data: &impl Serialize,
+    logo: bool,
+    event: &EventConfig,
+) -> Option<String> {
+    // Templates that couldn't be registered are reported already
+    if !HANDLEBARS.read().has_template(template) {
+        return None;
+    }
+    outbox::render(template, email_data, logo)
+        .err()
+        .map(|e| format!("{} with data of event {}: {}", template, event.id, e))
+}
+
+/// Registration with everything filled in, using the first class, target face and session
+fn sample_registration(event: &EventConfig) -> Registration {
+    let info = event.info();
+    Registration {
+        event: event.id,
+        waiting_list: false,
+        first_name: "Robin".to_string(),
+        last_name: "Hood".to_string(),
+        mail: "robin.hood@example.com".to_string(),
+        comment: "Kommentar".to_string(),
+        date_of_birth: NaiveDate::from_ymd_opt(2000, 1, 1),
+        gender: Some(Gender::Male),
+        class: info.classes.first().cloned(),
+        target_face: info.target_faces.first().copied(),
+        session: info.sessions.first().map_or(1, |session| session.id),
+    }
+}
+
+/// Runs the pending migrations in a transaction that is rolled back.
+/// Returns the number of pending migrations and the registrations not matching the rules.
+fn check_database() -> Result<(usize, Vec<String>), String> {
+    let database_url = std::env::var("DATABASE_URL").map_err(|_| {
+        "DATABASE_URL must be set via environment variable or --database-file".to_string()
+    })?;
+    // Connecting would create an empty database instead of reporting the wrong path
+    if !std::path::Path::new(&database_url).is_file() {
+        return Err(format!("Database {} doesn't exist", database_url));
+    }
+    let mut connection = SqliteConnection::establish(&database_url)
+        .map_err(|e| format!("Couldn't connect to database {}: {}", database_url, e))?;
+    connection
+        .begin_test_transaction()
+        .map_err(|e| format!("Couldn't start transaction in database: {}", e))?;
+    let applied = connection
+        .run_pending_migrations(MIGRATIONS)
+        .map_err(|e| format!("Couldn't migrate database: {}", e))?
+        .len();
+    let outdated = outdated_registrations(&mut connection)
+        .map_err(|e| format!("Couldn't load registrations: {}", e))?;
+    Ok((applied, outdated))
+}
+
+/// Registrations that were valid when stored but not with the current rules and events,
+/// e.g. after a class was removed
+fn outdated_registrations(conn: &mut SqliteConnection) -> crate::error::Result<Vec<String>> {
+    let mut outdated = Vec::new();
+    for stored in repository::list(conn, None)? {
+        // Old registrations lack the data to check them, removed events don't matter anymore
+        let (Some(archer), Ok(event)) = (stored.archer(), event::find_event(stored.event)) else {
+            continue;
+        };
+        if let Err(errors) = archer.validate(&event.info()) {
+            let errors: Vec<_> = errors.iter().map(|e| e.message_en()).collect();
+            outdated.push(format!(
+                "Registration {} doesn't match the rules of event {}: {}",
+                stored.bib,
+                event.id,
+                errors.join(", ")
+            ));
+        }
+    }
+    Ok(outdated)
+}
@@ -1,19 +1,124 @@
+use crate::auth::Role;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use common::{
+    class::Class,
+    event::{EventInfo, SessionInfo},
+    target_face::TargetFace,
+};
 use email_address::EmailAddress;
 use serde::{Deserialize, Serialize};
-use std::str::FromStr;
+use std::{path::PathBuf, str::FromStr};
+use strum::IntoEnumIterator;
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct Config {
     pub port: u16,
+    /// Nobody can register without events
+    #[serde(default)]
+    pub events: Vec<EventConfig>,
     pub mail_server: MailServerConfig,
     pub mail_message: MailMessageConfig,
+    #[serde(default)]
+    pub admins: Vec<AdminConfig>,
+    /// Address of the registration page, used for the links in the mails
+    #[serde(default)]
+    pub public_url: String,
+    /// Key for signing the self-service links in the mails
+    #[serde(default)]
+    pub link_secret: String,
+    /// Hours until unconfirmed registrations are deleted
+    #[serde(default = "default_verification_hours")]
+    pub verification_hours: u32,
+    /// TOML or JSON file with the class definitions. The built-in rules if omitted
+    pub class_rules: Option<PathBuf>,
+}
+
+fn default_verification_hours() -> u32 {
+    48
 }
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct MailServerConfig {
+    #[serde(default)]
+    pub transport: MailTransport,
+    #[serde(default)]
     pub smtp_server: String,
+    /// Defaults to the submission port for `relay` and to 25 for `smtp`
+    pub smtp_port: Option<u16>,
+    #[serde(default)]
     pub smtp_username: String,
+    #[serde(default)]
     pub smtp_password: String,
+    /// Directory the `file` transport writes the mails to
+    pub mail_directory: Option<PathBuf>,
+}
+
+/// How mails are delivered
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MailTransport {
+    /// SMTP server with TLS and credentials
+    #[default]
+    Relay,
+    /// SMTP server without TLS, e.g. a local mail catcher
+    Smtp,
+    /// One `.eml` file per mail in `mail_directory`
+    File,
+    /// Only writes the mails to the log
+    Log,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EventConfig {
+    pub id: i32,
+    pub name: String,
+    pub date: NaiveDate,
+    pub venue: String,
+    pub season_start: NaiveDate,
+    /// Classes offered at the event. All classes of the class rules if omitted
+    pub classes: Option<Vec<Class>>,
+    /// Target faces offered at the event. All target faces if omitted
+    pub target_faces: Option<Vec<TargetFace>>,
+    /// Maximum number of archers
+    pub capacity: Option<u32>,
+    /// Everybody shoots in session 1 if omitted
+    #[serde(default)]
+    pub sessions: Vec<SessionInfo>,
+    /// Local time from which on registrations are accepted, e.g. `2023-04-01T00:00:00`
+    pub registration_opens: Option<NaiveDateTime>,
+    /// Local time from which on registrations are rejected
+    pub registration_closes: Option<NaiveDateTime>,
+    /// Reject registrations when the event or session is full instead of putting them on the waiting list
+    #[serde(default)]
+    pub reject_when_full: bool,
+    /// Replaces the default mail template for registrations to this event
+    pub mail_template: Option<PathBuf>,
+    /// HTML version of `mail_template`
+    pub mail_html_template: Option<PathBuf>,
+}
+
+impl EventConfig {
+    pub fn info(&self) -> EventInfo {
+        EventInfo {
+            id: self.id,
+            name: self.name.clone(),
+            date: self.date,
+            venue: self.venue.clone(),
+            season_start: self.season_start,
+            classes: self
+                .classes
+                .clone()
+                .unwrap_or_else(|| Class::all_classes().collect()),
+            target_faces: self
+                .target_faces
+                .clone()
+                .unwrap_or_else(|| TargetFace::iter().collect()),
+            sessions: self.sessions.clone(),
+            registration_opens: self.registration_opens,
+            registration_closes: self.registration_closes,
+            server_time: None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -21,6 +126,39 @@ pub struct MailMessageConfig {
     pub sender_name: String,
     pub sender_address: EmailAddress,
     pub subject: String,
+    /// Subject of the mail to archers moving up from the waiting list. Defaults to `subject`
+    pub promotion_subject: Option<String>,
+    /// Subject of the mail confirming a withdrawn registration. Defaults to `subject`
+    pub withdrawal_subject: Option<String>,
+    /// Subject of the mail asking to confirm the email address. Defaults to `subject`
+    pub verification_subject: Option<String>,
+    /// Addresses of the organizers getting informed about new registrations
+    #[serde(default)]
+    pub organizer_addresses: Vec<EmailAddress>,
+    #[serde(default)]
+    pub organizer_mode: OrganizerMode,
+    /// Local time of the daily digest, e.g. `18:00:00`
+    #[serde(default = "default_digest_time")]
+    pub organizer_digest_time: NaiveTime,
+    /// Subject of the mails to the organizers. Defaults to `subject`
+    pub organizer_subject: Option<String>,
+    /// Image attached to HTML mails, shown with `<img src="cid:logo">`
+    pub logo: Option<PathBuf>,
+}
+
+fn default_digest_time() -> NaiveTime {
+    NaiveTime::from_hms_opt(18, 0, 0).unwrap()
+}
+
+/// How organizers learn about new registrations
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrganizerMode {
+    /// A mail for every registration as soon as it is confirmed
+    #[default]
+    Immediate,
+    /// One mail a day with all registrations confirmed since the last one
+    Digest,
 }
 
 impl Default for MailMessageConfig {
@@ -29,6 +167,41 @@ impl Default for MailMessageConfig {
             sender_name: String::new(),
             sender_address: EmailAddress::from_str("example@mail.com").unwrap(),
             subject: String::new(),
+            promotion_subject: None,
+            withdrawal_subject: None,
+            verification_subject: None,
+            organizer_addresses: Vec::new(),
+            organizer_mode: OrganizerMode::default(),
+            organizer_digest_time: default_digest_time(),
+            organizer_subject: None,
+            logo: None,
         }
     }
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct AdminConfig {
+    pub username: String,
+    /// Argon2 hash as printed by `backend hash-password`
+    #[serde(default)]
+    pub password_hash: String,
+    /// File containing the password hash. Overwrites `password_hash`
+    pub password_hash_file: Option<PathBuf>,
+    pub role: Role,
+}
+
+/// Event 1 with the given additional settings in TOML
+#[cfg(test)]
+pub fn test_event(settings: &str) -> EventConfig {
+    toml::from_str(&format!(
+        r#"
+        id = 1
+        name = "Vereinsmeisterschaft WA"
+        date = "2023-05-21"
+        venue = "Bogensportplatz"
+        season_start = "2023-01-01"
+        {settings}
+        "#
+    ))
+    .unwrap()
+}
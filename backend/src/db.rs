@@ -1,8 +1,27 @@
+use diesel::connection::SimpleConnection;
 use diesel::prelude::*;
 use diesel::Connection;
 
 pub fn establish_connection() -> SqliteConnection {
     let database_url = std::env::var("DATABASE_URL")
         .expect("DATABASE_URL must be set via environment variable or cli argument");
-    SqliteConnection::establish(&database_url).expect("Couldn't connect to database!")
+    let mut connection =
+        SqliteConnection::establish(&database_url).expect("Couldn't connect to database!");
+    // Registrations write in immediate transactions, concurrent requests have to wait for each other
+    connection
+        .batch_execute("PRAGMA busy_timeout = 5000;")
+        .expect("Couldn't configure database connection!");
+    connection
+}
+
+/// Empty in-memory database with all migrations applied
+#[cfg(test)]
+pub fn test_connection() -> SqliteConnection {
+    use diesel_migrations::MigrationHarness;
+
+    let mut connection = SqliteConnection::establish(":memory:").unwrap();
+    connection
+        .run_pending_migrations(crate::MIGRATIONS)
+        .unwrap();
+    connection
 }
@@ -1,10 +1,38 @@
-use axum::{http::StatusCode, response::IntoResponse};
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use chrono::NaiveDateTime;
+use common::archer::RegistrationError;
 use Error::*;
 
 /// All errors produced in the backend
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
-    MailError(lettre::transport::smtp::Error),
     DBError(diesel::result::Error),
+    MailError(lettre::transport::smtp::Error),
+    MailFileError(std::io::Error),
+    MailBuildError(lettre::error::Error),
+    TemplateError(handlebars::RenderError),
+    InvalidAddress(lettre::address::AddressError),
+    /// The mail couldn't be built from the stored data
+    InvalidMail(String),
+    MailConfigError(String),
+    ValidationError(Vec<RegistrationError>),
+    ExportError(rust_xlsxwriter::XlsxError),
+    UnknownColumn(String),
+    InvalidPage,
+    InvalidCredentials,
+    /// Failed logins of the username have to wait this long
+    TooManyLoginAttempts(std::time::Duration),
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    EventNotFound,
+    MailNotFound,
+    EventFull,
+    SessionFull,
+    RegistrationNotOpen(NaiveDateTime),
+    RegistrationClosed(NaiveDateTime),
+    InvalidLink,
+    VerificationExpired,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -12,11 +40,25 @@ pub type Result<T> = std::result::Result<T, Error>;
 impl IntoResponse for Error {
     fn into_response(self) -> axum::response::Response {
         match self {
-            Error::MailError(e) => {
-                log::warn!("{}", e);
+            Error::InvalidLink => {
+                (StatusCode::FORBIDDEN, "Der Link ist ungültig".to_string()).into_response()
+            }
+            Error::VerificationExpired => (
+                StatusCode::GONE,
+                "Der Bestätigungslink ist abgelaufen. Bitte melde dich erneut an.".to_string(),
+            )
+                .into_response(),
+            Error::MailError(_)
+            | Error::MailFileError(_)
+            | Error::MailBuildError(_)
+            | Error::TemplateError(_)
+            | Error::InvalidAddress(_)
+            | Error::InvalidMail(_)
+            | Error::MailConfigError(_) => {
+                log::warn!("{}", self);
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    "Bestätigungsmail konnte nicht abgeschickt werden.".to_string(),
+                    "Mail konnte nicht verschickt werden".to_string(),
                 )
                     .into_response()
             }
@@ -28,6 +70,134 @@ impl IntoResponse for Error {
                 )
                     .into_response()
             }
+            Error::ValidationError(errors) => {
+                let errors: Vec<_> = errors
+                    .iter()
+                    .map(|error| {
+                        let mut json = serde_json::to_value(error).unwrap();
+                        json["field"] = error.field().into();
+                        json["message"] = error.message_de().into();
+                        json["message_en"] = error.message_en().into();
+                        json
+                    })
+                    .collect();
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Json(serde_json::json!({ "errors": errors })),
+                )
+                    .into_response()
+            }
+            Error::ExportError(e) => {
+                log::error!("{}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Export konnte nicht erstellt werden".to_string(),
+                )
+                    .into_response()
+            }
+            Error::UnknownColumn(column) => (
+                StatusCode::BAD_REQUEST,
+                format!("Unbekannte Spalte \"{}\"", column),
+            )
+                .into_response(),
+            Error::InvalidPage => {
+                (StatusCode::BAD_REQUEST, "Ungültige Seite".to_string()).into_response()
+            }
+            Error::InvalidCredentials => (
+                StatusCode::UNAUTHORIZED,
+                "Benutzername oder Passwort falsch".to_string(),
+            )
+                .into_response(),
+            Error::TooManyLoginAttempts(wait) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                format!(
+                    "Zu viele fehlgeschlagene Anmeldungen, bitte warte {} Sekunden",
+                    wait.as_secs().max(1)
+                ),
+            )
+                .into_response(),
+            Error::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                "Nicht angemeldet oder Sitzung abgelaufen".to_string(),
+            )
+                .into_response(),
+            Error::Forbidden => (
+                StatusCode::FORBIDDEN,
+                "Keine Berechtigung für diese Aktion".to_string(),
+            )
+                .into_response(),
+            Error::NotFound => (
+                StatusCode::NOT_FOUND,
+                "Anmeldung nicht gefunden".to_string(),
+            )
+                .into_response(),
+            Error::EventNotFound => (
+                StatusCode::NOT_FOUND,
+                "Veranstaltung nicht gefunden".to_string(),
+            )
+                .into_response(),
+            Error::MailNotFound => {
+                (StatusCode::NOT_FOUND, "Mail nicht gefunden".to_string()).into_response()
+            }
+            Error::EventFull => (
+                StatusCode::CONFLICT,
+                "Die Veranstaltung ist leider ausgebucht".to_string(),
+            )
+                .into_response(),
+            Error::SessionFull => (
+                StatusCode::CONFLICT,
+                "Der gewählte Durchgang ist leider ausgebucht".to_string(),
+            )
+                .into_response(),
+            Error::RegistrationNotOpen(opens) => (
+                StatusCode::FORBIDDEN,
+                format!(
+                    "Die Anmeldung ist erst ab dem {} möglich",
+                    opens.format("%d.%m.%Y um %H:%M Uhr")
+                ),
+            )
+                .into_response(),
+            Error::RegistrationClosed(closed) => (
+                StatusCode::FORBIDDEN,
+                format!(
+                    "Die Anmeldung ist seit dem {} geschlossen",
+                    closed.format("%d.%m.%Y um %H:%M Uhr")
+                ),
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Description for the log. Users get the messages of [`IntoResponse`].
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DBError(e) => write!(f, "Database error: {e}"),
+            MailError(e) => write!(f, "SMTP error: {e}"),
+            MailFileError(e) => write!(f, "Couldn't write mail: {e}"),
+            MailBuildError(e) => write!(f, "Couldn't build mail: {e}"),
+            TemplateError(e) => write!(f, "Couldn't render mail template: {e}"),
+            InvalidAddress(e) => write!(f, "Invalid mail address: {e}"),
+            InvalidMail(e) => write!(f, "Invalid mail: {e}"),
+            MailConfigError(e) => write!(f, "Invalid mail config: {e}"),
+            ValidationError(errors) => write!(f, "{} validation errors", errors.len()),
+            ExportError(e) => write!(f, "Export error: {e}"),
+            UnknownColumn(column) => write!(f, "Unknown column {column}"),
+            InvalidPage => write!(f, "Invalid page"),
+            InvalidCredentials => write!(f, "Invalid credentials"),
+            TooManyLoginAttempts(wait) => write!(f, "Too many login attempts, wait {wait:?}"),
+            Unauthorized => write!(f, "Unauthorized"),
+            Forbidden => write!(f, "Forbidden"),
+            NotFound => write!(f, "Registration not found"),
+            EventNotFound => write!(f, "Event not found"),
+            MailNotFound => write!(f, "Mail not found"),
+            EventFull => write!(f, "Event full"),
+            SessionFull => write!(f, "Session full"),
+            RegistrationNotOpen(opens) => write!(f, "Registration opens {opens}"),
+            RegistrationClosed(closed) => write!(f, "Registration closed {closed}"),
+            InvalidLink => write!(f, "Invalid link"),
+            VerificationExpired => write!(f, "Verification expired"),
         }
     }
 }
@@ -38,6 +208,36 @@ impl From<lettre::transport::smtp::Error> for Error {
     }
 }
 
+impl From<lettre::error::Error> for Error {
+    fn from(e: lettre::error::Error) -> Self {
+        MailBuildError(e)
+    }
+}
+
+impl From<handlebars::RenderError> for Error {
+    fn from(e: handlebars::RenderError) -> Self {
+        TemplateError(e)
+    }
+}
+
+impl From<lettre::address::AddressError> for Error {
+    fn from(e: lettre::address::AddressError) -> Self {
+        InvalidAddress(e)
+    }
+}
+
+impl From<Vec<RegistrationError>> for Error {
+    fn from(e: Vec<RegistrationError>) -> Self {
+        ValidationError(e)
+    }
+}
+
+impl From<rust_xlsxwriter::XlsxError> for Error {
+    fn from(e: rust_xlsxwriter::XlsxError) -> Self {
+        ExportError(e)
+    }
+}
+
 impl From<diesel::result::Error> for Error {
     fn from(e: diesel::result::Error) -> Self {
         DBError(e)
@@ -0,0 +1,139 @@
+use crate::{config::EventConfig, error::*, models, schema, CONFIG};
+use axum::{extract::Path, Json};
+use chrono::Local;
+use common::{
+    class::ClassRules,
+    event::{EventInfo, RegistrationWindow},
+};
+use diesel::prelude::*;
+use std::path::{Path as FilePath, PathBuf};
+
+pub async fn list_events() -> Json<Vec<EventInfo>> {
+    Json(CONFIG.read().events.iter().map(public_info).collect())
+}
+
+/// Class rules, so the frontend offers the classes the backend accepts
+pub async fn class_rules() -> Json<ClassRules> {
+    Json(ClassRules::current().as_ref().clone())
+}
+
+pub async fn event_info(Path(id): Path<i32>) -> Result<Json<EventInfo>> {
+    Ok(Json(public_info(&find_event(id)?)))
+}
+
+/// Info for the frontend, with the server time to count down to the registration window from
+fn public_info(event: &EventConfig) -> EventInfo {
+    EventInfo {
+        server_time: Some(Local::now().naive_local()),
+        ..event.info()
+    }
+}
+
+pub fn find_event(id: i32) -> Result<EventConfig> {
+    CONFIG
+        .read()
+        .events
+        .iter()
+        .find(|event| event.id == id)
+        .cloned()
+        .ok_or(Error::EventNotFound)
+}
+
+/// Fails if the event doesn't accept registrations at the moment
+pub fn check_registration_window(event: &EventConfig) -> Result<()> {
+    match event.info().registration_window(Local::now().naive_local()) {
+        RegistrationWindow::NotYetOpen(opens) => Err(Error::RegistrationNotOpen(opens)),
+        RegistrationWindow::Open => Ok(()),
+        RegistrationWindow::Closed(closes) => Err(Error::RegistrationClosed(closes)),
+    }
+}
+
+/// Name of the Handlebars templates for the event's registration mail
+pub fn mail_template_name(event: &EventConfig) -> String {
+    format!("user_mail_{}", event.id)
+}
+
+/// Files of the text and the HTML template for the event's registration mail.
+/// Each falls back to the default on its own, so an event can replace only one of them.
+pub fn mail_template_files(
+    event: &EventConfig,
+    default_text: &FilePath,
+    default_html: Option<&FilePath>,
+) -> (PathBuf, Option<PathBuf>) {
+    (
+        event
+            .mail_template
+            .clone()
+            .unwrap_or_else(|| default_text.to_path_buf()),
+        event
+            .mail_html_template
+            .clone()
+            .or_else(|| default_html.map(FilePath::to_path_buf)),
+    )
+}
+
+/// Stores the configured events in the database so registrations can refer to them
+pub fn sync_events() -> Result<()> {
+    let mut connection = crate::db::establish_connection();
+    for event in &CONFIG.read().events {
+        diesel::replace_into(schema::events::table)
+            .values(models::Event {
+                id: event.id,
+                name: event.name.clone(),
+                date: event.date.to_string(),
+                venue: event.venue.clone(),
+            })
+            .execute(&mut connection)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_mail_template_files() {
+    let event = |templates: &str| -> EventConfig {
+        toml::from_str(&format!(
+            "id = 1\nname = \"Turnier\"\ndate = \"2023-06-01\"\nvenue = \"Halle\"\n\
+             season_start = \"2023-01-01\"\n{templates}"
+        ))
+        .unwrap()
+    };
+    let text = FilePath::new("user_mail.tpl");
+    let html = FilePath::new("user_mail.html");
+    let files = |event: &EventConfig, html| {
+        let (text, html) = mail_template_files(event, text, html);
+        (
+            text.to_str().unwrap().to_string(),
+            html.map(|html| html.to_str().unwrap().to_string()),
+        )
+    };
+
+    let default = event("");
+    assert_eq!(
+        files(&default, Some(html)),
+        ("user_mail.tpl".into(), Some("user_mail.html".into()))
+    );
+    assert_eq!(files(&default, None), ("user_mail.tpl".into(), None));
+
+    let own_text = event("mail_template = \"event.tpl\"");
+    assert_eq!(
+        files(&own_text, Some(html)),
+        ("event.tpl".into(), Some("user_mail.html".into()))
+    );
+    assert_eq!(files(&own_text, None), ("event.tpl".into(), None));
+
+    let own_html = event("mail_html_template = \"event.html\"");
+    assert_eq!(
+        files(&own_html, Some(html)),
+        ("user_mail.tpl".into(), Some("event.html".into()))
+    );
+    assert_eq!(
+        files(&own_html, None),
+        ("user_mail.tpl".into(), Some("event.html".into()))
+    );
+
+    let own_both = event("mail_template = \"event.tpl\"\nmail_html_template = \"event.html\"");
+    assert_eq!(
+        files(&own_both, Some(html)),
+        ("event.tpl".into(), Some("event.html".into()))
+    );
+}
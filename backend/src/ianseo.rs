@@ -0,0 +1,147 @@
+//! Export of the registrations as Ianseo participant list.
+//!
+//! Ianseo imports participants from a UTF-8 text file with one archer per line
+//! and tab separated columns in the order of the `archers` table.
+
+use crate::{auth::AdminSession, error::*, models::Archer, repository};
+use axum::{
+    extract::Query,
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::IntoResponse,
+    Extension,
+};
+use common::class::Class;
+use serde::Deserialize;
+use std::io::Write;
+
+#[derive(Deserialize, Debug)]
+pub struct ExportQuery {
+    event: Option<i32>,
+}
+
+pub async fn export(
+    Extension(admin): Extension<AdminSession>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse> {
+    let archers = tokio::task::spawn_blocking(move || load_archers(query.event))
+        .await
+        .unwrap()?;
+    log::info!(
+        "Admin {} exported {} archers",
+        admin.username,
+        archers.len()
+    );
+
+    let mut file = Vec::new();
+    write_participants(&archers, &mut file).expect("Writing to a Vec can't fail");
+    Ok((
+        [
+            (CONTENT_TYPE, "text/plain; charset=utf-8"),
+            (CONTENT_DISPOSITION, "attachment; filename=\"ianseo.txt\""),
+        ],
+        file,
+    ))
+}
+
+/// Loads all archers except the unconfirmed ones and the ones on the waiting list
+pub fn load_archers(event: Option<i32>) -> Result<Vec<Archer>> {
+    let mut connection = crate::db::establish_connection();
+    repository::list_ianseo_rows(&mut connection, event)
+}
+
+pub fn write_participants(archers: &[Archer], writer: &mut impl Write) -> std::io::Result<()> {
+    for archer in archers {
+        writeln!(writer, "{}", participant_line(archer))?;
+    }
+    Ok(())
+}
+
+fn participant_line(archer: &Archer) -> String {
+    let flag = |participates: i32| if participates != 0 { "1" } else { "0" };
+    [
+        archer.bib.to_string(),
+        archer.session.to_string(),
+        field(&archer.division),
+        field(&ianseo_class(&archer.class)),
+        if is_butt_assignment(&archer.target) {
+            archer.target.clone()
+        } else {
+            String::new()
+        },
+        flag(archer.individual_qualification).to_string(),
+        flag(archer.team_qualification).to_string(),
+        flag(archer.individual_final).to_string(),
+        flag(archer.team_final).to_string(),
+        flag(archer.mixed_team_final).to_string(),
+        field(&archer.last_name),
+        field(&archer.first_name),
+        archer.gender.map(|g| g.to_string()).unwrap_or_default(),
+        field(&archer.country_code),
+        field(&archer.country_name),
+        field(&archer.date_of_birth),
+        field(archer.subclass.as_deref().unwrap_or_default()),
+        field(archer.country_code_2.as_deref().unwrap_or_default()),
+        field(archer.country_name_2.as_deref().unwrap_or_default()),
+        field(archer.country_code_3.as_deref().unwrap_or_default()),
+        field(archer.country_name_3.as_deref().unwrap_or_default()),
+    ]
+    .join("\t")
+}
+
+/// Class as defined in Ianseo. Classes the program doesn't know are exported as they are.
+fn ianseo_class(code: &str) -> String {
+    Class::new(code)
+        .definition()
+        .map_or_else(|| code.to_string(), |definition| definition.ianseo_code)
+}
+
+/// Ianseo expects the butt number followed by the position, e.g. `12A`.
+/// Until butts are assigned the `target` column holds the target face, which Ianseo must not see.
+fn is_butt_assignment(target: &str) -> bool {
+    let mut chars = target.chars();
+    let Some(position) = chars.next_back() else {
+        return false;
+    };
+    let butt = chars.as_str();
+    position.is_ascii_uppercase() && !butt.is_empty() && butt.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Tabs and line breaks would break the column layout
+fn field(value: &str) -> String {
+    value.replace(['\t', '\r', '\n'], " ").trim().to_string()
+}
+
+#[test]
+fn test_participant_line() {
+    let archer = Archer {
+        bib: 7,
+        session: 1,
+        division: "B".to_string(),
+        class: "B210".to_string(),
+        target: "spot".to_string(),
+        individual_qualification: 1,
+        team_qualification: 1,
+        individual_final: 1,
+        team_final: 0,
+        mixed_team_final: 1,
+        last_name: "Hood".to_string(),
+        first_name: "Robin\t".to_string(),
+        gender: Some(0),
+        country_code: "PSV".to_string(),
+        country_name: "PSV München".to_string(),
+        date_of_birth: "1990-06-01".to_string(),
+        subclass: None,
+        country_code_2: None,
+        country_name_2: None,
+        country_code_3: None,
+        country_name_3: None,
+    };
+    assert!(is_butt_assignment("12A"));
+    assert!(!is_butt_assignment("spot"));
+    assert!(!is_butt_assignment("A"));
+    assert!(!is_butt_assignment("40ü"));
+    assert_eq!(
+        participant_line(&archer),
+        "7\t1\tB\tB10\t\t1\t1\t1\t0\t1\tHood\tRobin\t0\tPSV\tPSV München\t1990-06-01\t\t\t\t\t"
+    );
+}
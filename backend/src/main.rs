@@ -1,25 +1,41 @@
 use axum::{
     body::{boxed, Body, BoxBody},
     http::{Request, Response, StatusCode, Uri},
-    routing::{get, post},
+    middleware,
+    routing::{delete, get, post},
     Router,
 };
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use common::class::ClassRules;
 use config::Config;
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use handlebars::Handlebars;
 use lazy_static::lazy_static;
 use static_init::dynamic;
-use std::{net::SocketAddr, path::PathBuf};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
 use tower::ServiceExt;
 use tower_http::services::ServeDir;
 
 mod archer;
+mod auth;
+mod check;
 mod config;
 mod db;
 mod error;
+mod event;
+mod ianseo;
 mod models;
+mod organizer;
+mod outbox;
+mod registration;
+mod repository;
 mod schema;
+mod spreadsheet;
+mod transport;
+mod verification;
 
 #[dynamic()]
 pub static mut CONFIG: Config = Config::default();
@@ -27,6 +43,10 @@ pub static mut CONFIG: Config = Config::default();
 #[dynamic()]
 pub static mut HANDLEBARS: Handlebars<'static> = Handlebars::new();
 
+/// HTML versions of the templates in [`HANDLEBARS`], registered under the same names
+#[dynamic()]
+pub static mut HTML_HANDLEBARS: Handlebars<'static> = Handlebars::new();
+
 #[derive(Parser, Debug)]
 struct CliArgs {
     /// Path to config file
@@ -41,10 +61,57 @@ struct CliArgs {
     #[arg(long, default_value_t = String::from("user_mail.tpl"))]
     mail_template_file: String,
 
+    /// Path to the HTML version of the email template. Mails are sent as plain text if omitted
+    #[arg(long)]
+    mail_html_template_file: Option<PathBuf>,
+
+    /// Path to the template of the mail to archers moving up from the waiting list
+    #[arg(long, default_value_t = String::from("promotion_mail.tpl"))]
+    promotion_mail_template_file: String,
+
+    /// Path to the template of the mail confirming a withdrawn registration
+    #[arg(long, default_value_t = String::from("withdrawal_mail.tpl"))]
+    withdrawal_mail_template_file: String,
+
+    /// Path to the template of the mail asking to confirm the email address
+    #[arg(long, default_value_t = String::from("verification_mail.tpl"))]
+    verification_mail_template_file: String,
+
+    /// Path to the template of the mail informing the organizers about new registrations
+    #[arg(long, default_value_t = String::from("organizer_mail.tpl"))]
+    organizer_mail_template_file: String,
+
     /// Path to email password file.
     /// Overwrites password from config
     #[arg(long)]
     mail_password_file: Option<PathBuf>,
+
+    /// Path to a file with the key for signing the links in the mails.
+    /// Overwrites `link_secret` from config
+    #[arg(long)]
+    link_secret_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the registration server (default)
+    Serve,
+    /// Read a password from stdin and print its hash for the `admins` section of the config
+    HashPassword,
+    /// Check the config, the mail templates, the mail server and the database without serving
+    CheckConfig,
+    /// Write all registrations as Ianseo participant list
+    ExportIanseo {
+        /// Output file. Prints to stdout if omitted
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Only export archers registered for this event
+        #[arg(long)]
+        event: Option<i32>,
+    },
 }
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
@@ -53,36 +120,87 @@ pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 async fn main() {
     env_logger::init();
     let args = CliArgs::parse();
-    if let Some(db_file) = args.database_file {
+    if let Some(Command::HashPassword) = args.command {
+        let mut password = String::new();
+        std::io::stdin()
+            .read_line(&mut password)
+            .expect("Couldn't read password from stdin");
+        println!(
+            "{}",
+            auth::hash_password(password.trim_end_matches(['\r', '\n']))
+        );
+        return;
+    }
+    if let Some(db_file) = &args.database_file {
         std::env::set_var("DATABASE_URL", db_file);
     }
+    if let Some(Command::CheckConfig) = args.command {
+        let ok = check::check_config(&args).await;
+        std::process::exit(if ok { 0 } else { 1 });
+    }
     db::establish_connection()
         .run_pending_migrations(MIGRATIONS)
         .expect("Could not migrate database");
-
-    *CONFIG.write() = {
-        let mut config = load_config(&std::path::PathBuf::from(&args.config_file));
-        if let Some(pswd) = args.mail_password_file {
-            config.mail_server.smtp_password =
-                std::fs::read_to_string(pswd).expect("Password file couldn't be read");
+    let mut config = load_config(&args).unwrap_or_else(|e| panic!("{}", e));
+    // Also needed by the export, which takes the Ianseo classes from the rules
+    ClassRules::set_current(load_class_rules(&config).unwrap_or_else(|e| panic!("{}", e)));
+    if let Some(Command::ExportIanseo { output, event }) = &args.command {
+        let archers = ianseo::load_archers(*event)
+            .unwrap_or_else(|_| panic!("Couldn't load archers from database"));
+        match output {
+            Some(path) => ianseo::write_participants(
+                &archers,
+                &mut std::fs::File::create(path)
+                    .unwrap_or_else(|_| panic!("Couldn't create file {:?}", path)),
+            ),
+            None => ianseo::write_participants(&archers, &mut std::io::stdout().lock()),
         }
-        config
-    };
-    {
-        let mut handlebars = HANDLEBARS.write();
-        handlebars.set_strict_mode(true);
-        handlebars.set_dev_mode(cfg!(debug_assertions));
-        handlebars
-            .register_template_file(
-                "user_mail",
-                std::path::PathBuf::from(args.mail_template_file),
-            )
-            .unwrap();
+        .expect("Couldn't write Ianseo export");
+        return;
+    }
+
+    if config.link_secret.is_empty() {
+        log::warn!("No link_secret configured, links in mails become invalid on restart");
+        config.link_secret = auth::random_token();
+    }
+    if config.events.is_empty() {
+        log::warn!("No events configured, nobody can register");
     }
+    let template_problems = register_templates(&args, &config);
+    if !template_problems.is_empty() {
+        panic!("{}", template_problems.join("\n"));
+    }
+    *CONFIG.write() = config;
+    let mailer = transport::Mailer::new(&CONFIG.read()).unwrap_or_else(|e| panic!("{}", e));
+    event::sync_events().unwrap_or_else(|_| panic!("Couldn't store events in database"));
+    tokio::spawn(verification::remove_expired_registrations());
+    tokio::spawn(outbox::deliver_mails(mailer));
+    tokio::spawn(organizer::send_digests());
 
+    let admin = Router::new()
+        .route("/archers", get(archer::list_archers))
+        .route("/archers/:bib", delete(archer::delete_archer))
+        .route("/events/:id/archers", get(archer::list_event_archers))
+        .route("/export/ianseo", get(ianseo::export))
+        .route("/export/participants", get(spreadsheet::export))
+        .route("/mails/failed", get(outbox::list_failed_mails))
+        .route("/mails/:id/resend", post(outbox::resend_mail))
+        .route("/logout", post(auth::logout))
+        .route_layer(middleware::from_fn(auth::require_login))
+        .route("/login", post(auth::login));
     let api = Router::new()
-        .route("/archers", post(archer::create_archer))
-        .route("/archers", get(archer::list_archers));
+        .route("/events", get(event::list_events))
+        .route("/events/:id", get(event::event_info))
+        .route("/classes", get(event::class_rules))
+        .route("/events/:id/archers", post(archer::create_archer))
+        .route("/verifications/:token", post(verification::verify))
+        .route(
+            "/registrations/:token",
+            get(registration::get_registration)
+                .put(registration::update_registration)
+                .delete(registration::withdraw_registration),
+        )
+        .nest("/admin", admin);
     let app = Router::new()
         .nest_service("/", get(handler))
         .nest_service("/api", api);
@@ -90,7 +208,7 @@ async fn main() {
     let addr = SocketAddr::from(([0, 0, 0, 0], CONFIG.read().port));
     println!("listening on {}", addr);
     axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 }
@@ -128,8 +246,145 @@ async fn get_static_file(uri: Uri) -> Result<Response<BoxBody>, (StatusCode, Str
     }
 }
 
-fn load_config(path: &std::path::Path) -> Config {
+/// Reads the config file and the secrets given in separate files
+fn load_config(args: &CliArgs) -> Result<Config, String> {
+    let path = &args.config_file;
     let toml_config = std::fs::read_to_string(path)
-        .unwrap_or_else(|_| panic!("Couldn't read file from path {:?}", path));
-    toml::from_str(&toml_config).expect("Couldn't parse config content!")
+        .map_err(|e| format!("Couldn't read config file {:?}: {}", path, e))?;
+    let toml_config: toml::Value = toml::from_str(&toml_config)
+        .map_err(|e| format!("Couldn't parse config file {:?}: {}", path, e))?;
+    // Configs of versions with a single event would otherwise run without any
+    if toml_config.get("event").is_some() {
+        return Err(format!(
+            "Config file {:?} has an [event] section, which was replaced by [[events]] with an id for each event",
+            path
+        ));
+    }
+    let mut config: Config = toml_config
+        .try_into()
+        .map_err(|e| format!("Couldn't parse config file {:?}: {}", path, e))?;
+    if let Some(pswd) = &args.mail_password_file {
+        config.mail_server.smtp_password = std::fs::read_to_string(pswd)
+            .map_err(|e| format!("Password file {:?} couldn't be read: {}", pswd, e))?;
+    }
+    if let Some(secret_file) = &args.link_secret_file {
+        config.link_secret = std::fs::read_to_string(secret_file)
+            .map_err(|e| format!("Link secret file {:?} couldn't be read: {}", secret_file, e))?
+            .trim()
+            .to_string();
+    }
+    for admin in &mut config.admins {
+        if let Some(hash_file) = &admin.password_hash_file {
+            admin.password_hash = std::fs::read_to_string(hash_file)
+                .map_err(|e| {
+                    format!(
+                        "Password hash file {:?} of admin {} couldn't be read: {}",
+                        hash_file, admin.username, e
+                    )
+                })?
+                .trim()
+                .to_string();
+        }
+    }
+    Ok(config)
+}
+
+/// Reads the class rules file of the config, TOML or JSON depending on the extension
+fn load_class_rules(config: &Config) -> Result<ClassRules, String> {
+    let rules = match &config.class_rules {
+        Some(path) => {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| format!("Couldn't read class rules {:?}: {}", path, e))?;
+            if path.extension().is_some_and(|ext| ext == "json") {
+                serde_json::from_str::<ClassRules>(&content)
+                    .map_err(|e| e.to_string())
+                    .and_then(|rules| rules.validate().map(|()| rules))
+            } else {
+                ClassRules::from_toml(&content)
+            }
+            .map_err(|e| format!("Invalid class rules {:?}: {}", path, e))?
+        }
+        None => ClassRules::default(),
+    };
+    for event in &config.events {
+        for cls in event.classes.iter().flatten() {
+            if rules.get(cls).is_none() {
+                return Err(format!(
+                    "Event {} offers the class {}, which the class rules don't define",
+                    event.id,
+                    cls.code()
+                ));
+            }
+        }
+    }
+    Ok(rules)
+}
+
+/// Registers the mail templates in [`HANDLEBARS`] and [`HTML_HANDLEBARS`].
+/// Returns the templates that couldn't be read or parsed.
+fn register_templates(args: &CliArgs, config: &Config) -> Vec<String> {
+    let mut handlebars = HANDLEBARS.write();
+    handlebars.set_strict_mode(true);
+    handlebars.set_dev_mode(cfg!(debug_assertions));
+    // Plain text, escaping HTML would garble links and names
+    handlebars.register_escape_fn(handlebars::no_escape);
+    let mut html_handlebars = HTML_HANDLEBARS.write();
+    html_handlebars.set_strict_mode(true);
+    html_handlebars.set_dev_mode(cfg!(debug_assertions));
+
+    let mut text_templates = vec![
+        (
+            "user_mail".to_string(),
+            PathBuf::from(&args.mail_template_file),
+        ),
+        (
+            "promotion_mail".to_string(),
+            PathBuf::from(&args.promotion_mail_template_file),
+        ),
+        (
+            "organizer_mail".to_string(),
+            PathBuf::from(&args.organizer_mail_template_file),
+        ),
+        (
+            "verification_mail".to_string(),
+            PathBuf::from(&args.verification_mail_template_file),
+        ),
+        (
+            "withdrawal_mail".to_string(),
+            PathBuf::from(&args.withdrawal_mail_template_file),
+        ),
+    ];
+    let mut html_templates = Vec::new();
+    if let Some(html_template) = &args.mail_html_template_file {
+        html_templates.push(("user_mail".to_string(), html_template.clone()));
+    }
+    for event in &config.events {
+        let (text_template, html_template) = event::mail_template_files(
+            event,
+            Path::new(&args.mail_template_file),
+            args.mail_html_template_file.as_deref(),
+        );
+        let name = event::mail_template_name(event);
+        text_templates.push((name.clone(), text_template));
+        if let Some(html_template) = html_template {
+            html_templates.push((name, html_template));
+        }
+    }
+
+    let mut problems = Vec::new();
+    for (registry, templates) in [
+        (&mut *handlebars, text_templates),
+        (&mut *html_handlebars, html_templates),
+    ] {
+        for (name, path) in templates {
+            if let Err(e) = registry.register_template_file(&name, &path) {
+                // The default templates are registered for every event without its own
+                let problem = format!("Template {:?} is invalid: {}", path, e);
+                if !problems.contains(&problem) {
+                    problems.push(problem);
+                }
+            }
+        }
+    }
+    problems
 }
@@ -1,5 +1,8 @@
-use crate::schema::{archer_additions, archers};
+use crate::schema::{archer_additions, archers, events, outbox};
+use chrono::NaiveDate;
+use common::{class::Class, target_face::TargetFace};
 use diesel::prelude::*;
+use serde::Serialize;
 
 #[derive(Queryable)]
 pub struct Archer {
@@ -10,7 +13,7 @@ pub struct Archer {
     pub target: String,
     pub individual_qualification: i32,
     pub team_qualification: i32,
-    pub individual_finale: i32,
+    pub individual_final: i32,
     pub team_final: i32,
     pub mixed_team_final: i32,
     pub last_name: String,
@@ -20,10 +23,10 @@ pub struct Archer {
     pub country_name: String,
     pub date_of_birth: String,
     pub subclass: Option<String>,
-    pub country_code2: String,
-    pub country_name2: String,
-    pub country_code3: String,
-    pub country_name3: String,
+    pub country_code_2: Option<String>,
+    pub country_name_2: Option<String>,
+    pub country_code_3: Option<String>,
+    pub country_name_3: Option<String>,
 }
 
 #[derive(Insertable, Default)]
@@ -57,4 +60,140 @@ pub struct ArcherAdditions {
     pub bib: i32,
     pub email: String,
     pub comment: String,
+    pub event: i32,
+    pub status: String,
+    pub verification_token: Option<String>,
+    /// Local time formatted with [`TIMESTAMP_FORMAT`]
+    pub verification_expires: Option<String>,
+    /// Whether the organizers got a mail about the registration
+    pub organizer_notified: bool,
+}
+
+/// Format of the dates of birth in the archers table, as Ianseo expects them
+pub const DATE_OF_BIRTH_FORMAT: &str = "%Y-%m-%d";
+
+/// Format of timestamps in the database. Sorts like the time it represents.
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Value of the `status` column of `archer_additions`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// The email address isn't confirmed yet
+    Pending,
+    Registered,
+    /// On the waiting list because the event or session was full
+    Waiting,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Pending => "pending",
+            Status::Registered => "registered",
+            Status::Waiting => "waiting",
+        }
+    }
+    pub fn parse(value: &str) -> Option<Self> {
+        [Status::Pending, Status::Registered, Status::Waiting]
+            .into_iter()
+            .find(|status| status.as_str() == value)
+    }
+}
+
+#[derive(Insertable, Queryable)]
+#[diesel(table_name = events)]
+pub struct Event {
+    pub id: i32,
+    pub name: String,
+    pub date: String,
+    pub venue: String,
+}
+
+/// A mail waiting in the outbox. Sent mails are deleted.
+#[derive(Insertable)]
+#[diesel(table_name = outbox)]
+pub struct NewMail {
+    pub recipient_name: String,
+    pub recipient: String,
+    pub subject: String,
+    pub template: String,
+    /// Template data as JSON
+    pub data: String,
+    pub status: String,
+    pub next_attempt: String,
+    pub created: String,
+    pub attachment_name: Option<String>,
+    /// MIME type of the attachment
+    pub attachment_type: Option<String>,
+    pub attachment: Option<String>,
+}
+
+#[derive(Queryable, Serialize)]
+pub struct OutboxMail {
+    pub id: i32,
+    pub recipient_name: String,
+    pub recipient: String,
+    pub subject: String,
+    pub template: String,
+    #[serde(skip)]
+    pub data: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt: String,
+    pub last_error: Option<String>,
+    pub created: String,
+    pub attachment_name: Option<String>,
+    #[serde(skip)]
+    pub attachment_type: Option<String>,
+    #[serde(skip)]
+    pub attachment: Option<String>,
+}
+
+/// Value of the `status` column of `outbox`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MailStatus {
+    /// Waiting for the next attempt
+    Queued,
+    /// Gave up after too many attempts, only sent again on request of an admin
+    Failed,
+}
+
+impl MailStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MailStatus::Queued => "queued",
+            MailStatus::Failed => "failed",
+        }
+    }
+}
+
+/// An entry of the archer list joined with its [`ArcherAdditions`]
+#[derive(Queryable, Serialize)]
+pub struct RegisteredArcher {
+    pub bib: i32,
+    pub session: i32,
+    pub division: String,
+    pub class: String,
+    pub target: String,
+    pub last_name: String,
+    pub first_name: String,
+    /// Formatted with [`DATE_OF_BIRTH_FORMAT`], empty if unknown
+    pub date_of_birth: String,
+    pub email: Option<String>,
+    pub comment: Option<String>,
+    pub event: Option<i32>,
+    pub status: Option<String>,
+}
+
+impl RegisteredArcher {
+    pub fn date_of_birth(&self) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(&self.date_of_birth, DATE_OF_BIRTH_FORMAT).ok()
+    }
+    pub fn class(&self) -> Option<Class> {
+        self.class.parse().ok()
+    }
+    pub fn target_face(&self) -> Option<TargetFace> {
+        self.target.parse().ok()
+    }
 }
@@ -0,0 +1,260 @@
+//! Mails informing the organizers about new registrations, either one per registration
+//! or a daily digest grouped by class. Registrations count as new once they are confirmed.
+
+use crate::{
+    archer, config::OrganizerMode, error::*, event, models::Status, outbox, repository, schema,
+    CONFIG,
+};
+use chrono::{Duration, Local, NaiveDateTime, NaiveTime};
+use common::archer::Registration;
+use diesel::prelude::*;
+use email_address::EmailAddress;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Entry of the organizer mail
+#[derive(Serialize)]
+struct RegistrationEntry {
+    event_name: String,
+    first_name: String,
+    last_name: String,
+    mail: String,
+    date_of_birth: String,
+    target_face: String,
+    session: String,
+    comment: String,
+    waiting_list: bool,
+}
+
+#[derive(Serialize)]
+struct ClassGroup {
+    class: String,
+    registrations: Vec<RegistrationEntry>,
+}
+
+#[derive(Serialize)]
+struct OrganizerMail {
+    digest: bool,
+    count: usize,
+    classes: Vec<ClassGroup>,
+}
+
+/// Informs the organizers about a newly confirmed registration, unless they get a digest
+pub fn registration_confirmed(conn: &mut SqliteConnection, bib: i32) -> Result<()> {
+    if CONFIG.read().mail_message.organizer_mode == OrganizerMode::Digest {
+        return Ok(());
+    }
+    let registration: Registration = repository::load(conn, bib)?.into();
+    let (addresses, subject) = organizer_recipients();
+    queue_organizer_mail(conn, &addresses, &subject, false, vec![registration])?;
+    mark_notified(conn, &[bib])
+}
+
+/// Sends the digest every day at the configured time. Runs forever.
+pub async fn send_digests() {
+    loop {
+        let now = Local::now().naive_local();
+        let next = next_digest(now, CONFIG.read().mail_message.organizer_digest_time);
+        tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+
+        if CONFIG.read().mail_message.organizer_mode != OrganizerMode::Digest {
+            continue;
+        }
+        let queued = tokio::task::spawn_blocking(|| {
+            let (addresses, subject) = organizer_recipients();
+            queue_digest(&mut crate::db::establish_connection(), &addresses, &subject)
+        });
+        match queued.await.unwrap() {
+            Ok(0) => {}
+            Ok(count) => {
+                log::info!("Queued digest with {} registrations", count);
+                outbox::wake();
+            }
+            Err(_) => log::error!("Couldn't queue the digest for the organizers"),
+        }
+    }
+}
+
+/// First time after `now` at the given time of day
+fn next_digest(now: NaiveDateTime, time: NaiveTime) -> NaiveDateTime {
+    let today = now.date().and_time(time);
+    if today > now {
+        today
+    } else {
+        today + Duration::days(1)
+    }
+}
+
+/// Queues the digest of all confirmed registrations the organizers don't know yet.
+/// Returns the number of these registrations.
+fn queue_digest(
+    conn: &mut SqliteConnection,
+    addresses: &[EmailAddress],
+    subject: &str,
+) -> Result<usize> {
+    use schema::archer_additions;
+
+    conn.immediate_transaction(|conn| -> Result<usize> {
+        let bibs: Vec<i32> = archer_additions::table
+            .filter(archer_additions::organizer_notified.eq(false))
+            .filter(archer_additions::status.ne(Status::Pending.as_str()))
+            .order(archer_additions::bib.asc())
+            .select(archer_additions::bib)
+            .load(conn)?;
+        if bibs.is_empty() {
+            return Ok(0);
+        }
+        let registrations = bibs
+            .iter()
+            .map(|&bib| repository::load(conn, bib).map(Registration::from))
+            .collect::<Result<Vec<_>>>()?;
+        queue_organizer_mail(conn, addresses, subject, true, registrations)?;
+        mark_notified(conn, &bibs)?;
+        Ok(bibs.len())
+    })
+}
+
+fn mark_notified(conn: &mut SqliteConnection, bibs: &[i32]) -> Result<()> {
+    use schema::archer_additions;
+
+    diesel::update(archer_additions::table.filter(archer_additions::bib.eq_any(bibs)))
+        .set(archer_additions::organizer_notified.eq(true))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Addresses and subject of the organizer mails
+fn organizer_recipients() -> (Vec<EmailAddress>, String) {
+    let config = CONFIG.read();
+    (
+        config.mail_message.organizer_addresses.clone(),
+        config
+            .mail_message
+            .organizer_subject
+            .clone()
+            .unwrap_or_else(|| config.mail_message.subject.clone()),
+    )
+}
+
+fn queue_organizer_mail(
+    conn: &mut SqliteConnection,
+    addresses: &[EmailAddress],
+    subject: &str,
+    digest: bool,
+    registrations: Vec<Registration>,
+) -> Result<()> {
+    if addresses.is_empty() {
+        return Ok(());
+    }
+
+    let email_data = organizer_mail_data(digest, registrations);
+    for address in addresses {
+        outbox::enqueue(
+            conn,
+            String::new(),
+            address.as_str(),
+            subject,
+            "organizer_mail",
+            &email_data,
+        )?;
+    }
+    Ok(())
+}
+
+/// Data for the organizer mail template, the registrations grouped by class
+pub fn organizer_mail_data(digest: bool, registrations: Vec<Registration>) -> impl Serialize {
+    let count = registrations.len();
+    let mut classes: BTreeMap<String, Vec<RegistrationEntry>> = BTreeMap::new();
+    for registration in registrations {
+        let event = event::find_event(registration.event).ok();
+        let class = registration.class.map(|cls| cls.name()).unwrap_or_default();
+        classes.entry(class).or_default().push(RegistrationEntry {
+            event_name: event
+                .as_ref()
+                .map(|event| event.name.clone())
+                .unwrap_or_default(),
+            session: event
+                .as_ref()
+                .map(|event| archer::session_description(event, registration.session))
+                .unwrap_or_default(),
+            date_of_birth: registration
+                .date_of_birth
+                .map(|dob| dob.format("%d.%m.%Y").to_string())
+                .unwrap_or_default(),
+            target_face: registration
+                .target_face
+                .map(|tf| tf.to_string())
+                .unwrap_or_default(),
+            first_name: registration.first_name,
+            last_name: registration.last_name,
+            mail: registration.mail,
+            comment: registration.comment,
+            waiting_list: registration.waiting_list,
+        });
+    }
+    OrganizerMail {
+        digest,
+        count,
+        classes: classes
+            .into_iter()
+            .map(|(class, registrations)| ClassGroup {
+                class,
+                registrations,
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn test_next_digest() {
+    let time = NaiveTime::from_hms_opt(18, 0, 0).unwrap();
+    let morning = chrono::NaiveDate::from_ymd_opt(2023, 5, 1)
+        .unwrap()
+        .and_hms_opt(9, 0, 0)
+        .unwrap();
+    assert_eq!(next_digest(morning, time), morning.date().and_time(time));
+    let evening = morning.date().and_time(time);
+    assert_eq!(next_digest(evening, time), evening + Duration::days(1));
+}
+
+#[test]
+fn test_queue_digest() {
+    use crate::{models::OutboxMail, schema::outbox};
+    use std::str::FromStr;
+
+    let mut conn = crate::db::test_connection();
+    let event = crate::config::test_event("");
+    let mut save = |mail, status| {
+        let bib = archer::save_archer(&mut conn, repository::test_archer(mail, 1), &event)
+            .ok()
+            .unwrap();
+        if let Some(status) = status {
+            repository::set_status(&mut conn, bib, status).ok().unwrap();
+        }
+    };
+    save("robin@example.com", Some(Status::Registered));
+    save("marian@example.com", Some(Status::Waiting));
+    // Unconfirmed registrations wait for the next digest
+    save("john@example.com", None);
+
+    let addresses = [EmailAddress::from_str("orga@example.com").unwrap()];
+    assert_eq!(
+        queue_digest(&mut conn, &addresses, "Neue Anmeldungen").ok(),
+        Some(2)
+    );
+    let mails: Vec<OutboxMail> = outbox::table
+        .filter(outbox::template.eq("organizer_mail"))
+        .load(&mut conn)
+        .unwrap();
+    assert_eq!(mails.len(), 1);
+    assert_eq!(mails[0].recipient, "orga@example.com");
+    let data: serde_json::Value = serde_json::from_str(&mails[0].data).unwrap();
+    assert_eq!(data["count"], 2);
+    assert_eq!(data["digest"], true);
+
+    // Every registration is only in one digest
+    assert_eq!(
+        queue_digest(&mut conn, &addresses, "Neue Anmeldungen").ok(),
+        Some(0)
+    );
+}
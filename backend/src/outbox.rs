@@ -0,0 +1,385 @@
+//! Outbox for all mails to archers.
+//!
+//! Mails are stored in the same transaction as the change they announce and sent by
+//! [`deliver_mails`] in the background, so a broken mail server doesn't fail a registration.
+//! Failed attempts are retried with exponential backoff. After [`MAX_ATTEMPTS`] the mail is
+//! marked as failed and waits for an admin to resend it.
+//!
+//! A mail is sent as HTML with the text as fallback if [`HTML_HANDLEBARS`] has a template
+//! with the name of the text template. HTML templates get the flag `logo` in addition.
+
+use crate::{
+    auth::Editor,
+    error::*,
+    models::{MailStatus, NewMail, OutboxMail, TIMESTAMP_FORMAT},
+    schema,
+    transport::Mailer,
+    HANDLEBARS, HTML_HANDLEBARS,
+};
+use axum::{extract::Path, http::StatusCode, response::IntoResponse, Json};
+use chrono::{Duration, Local};
+use diesel::prelude::*;
+use lettre::message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::{address::Envelope, Address, Message};
+use serde::Serialize;
+use tokio::sync::Notify;
+
+/// Attempts before a mail is marked as failed
+const MAX_ATTEMPTS: i32 = 10;
+/// Minutes to wait after the first failed attempt, doubled with every further attempt
+const FIRST_RETRY_MINUTES: i64 = 1;
+const MAX_RETRY_MINUTES: i64 = 6 * 60;
+/// Check for due retries this often even without new mails
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+static NEW_MAIL: Notify = Notify::const_new();
+
+fn now() -> String {
+    Local::now()
+        .naive_local()
+        .format(TIMESTAMP_FORMAT)
+        .to_string()
+}
+
+/// A text file attached to a mail
+pub struct MailAttachment {
+    pub filename: String,
+    /// MIME type, e.g. `text/calendar`
+    pub content_type: String,
+    pub content: String,
+}
+
+/// Stores a mail for sending. The template is rendered with `email_data` when the mail is sent.
+pub fn enqueue(
+    conn: &mut SqliteConnection,
+    name: String,
+    address: &str,
+    subject: &str,
+    template: &str,
+    email_data: &impl Serialize,
+) -> Result<()> {
+    enqueue_with_attachment(conn, name, address, subject, template, email_data, None)
+}
+
+pub fn enqueue_with_attachment(
+    conn: &mut SqliteConnection,
+    name: String,
+    address: &str,
+    subject: &str,
+    template: &str,
+    email_data: &impl Serialize,
+    attachment: Option<MailAttachment>,
+) -> Result<()> {
+    let (attachment_name, attachment_type, attachment) = match attachment {
+        Some(a) => (Some(a.filename), Some(a.content_type), Some(a.content)),
+        None => (None, None, None),
+    };
+    diesel::insert_into(schema::outbox::table)
+        .values(NewMail {
+            recipient_name: name,
+            recipient: address.to_string(),
+            subject: subject.to_string(),
+            template: template.to_string(),
+            data: serde_json::to_string(email_data).expect("Template data is always valid JSON"),
+            status: MailStatus::Queued.as_str().to_string(),
+            next_attempt: now(),
+            created: now(),
+            attachment_name,
+            attachment_type,
+            attachment,
+        })
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Makes [`deliver_mails`] send new mails right away. Call after committing the transaction.
+pub fn wake() {
+    NEW_MAIL.notify_one();
+}
+
+/// Sends due mails from the outbox. Runs forever.
+pub async fn deliver_mails(mailer: Mailer) {
+    loop {
+        let due =
+            tokio::task::spawn_blocking(|| load_due_mails(&mut crate::db::establish_connection()));
+        match due.await.unwrap() {
+            Ok(mails) => {
+                for mail in mails {
+                    let result = send_mail(&mailer, &mail).await;
+                    if let Err(e) = &result {
+                        log::warn!(
+                            "Couldn't send mail {} to {}: {}",
+                            mail.id,
+                            mail.recipient,
+                            e
+                        );
+                    }
+                    let id = mail.id;
+                    let attempts = mail.attempts + 1;
+                    let update = tokio::task::spawn_blocking(move || {
+                        let mut connection = crate::db::establish_connection();
+                        match result {
+                            Ok(()) => remove_mail(&mut connection, id),
+                            Err(e) => record_failure(&mut connection, id, attempts, e.to_string()),
+                        }
+                    });
+                    if update.await.unwrap().is_err() {
+                        log::error!("Couldn't update mail {} in the outbox", id);
+                    }
+                }
+            }
+            Err(_) => log::error!("Couldn't load mails from the outbox"),
+        }
+        let _ = tokio::time::timeout(POLL_INTERVAL, NEW_MAIL.notified()).await;
+    }
+}
+
+fn load_due_mails(conn: &mut SqliteConnection) -> Result<Vec<OutboxMail>> {
+    use schema::outbox;
+
+    Ok(outbox::table
+        .filter(outbox::status.eq(MailStatus::Queued.as_str()))
+        .filter(outbox::next_attempt.le(now()))
+        .order(outbox::id.asc())
+        .load(conn)?)
+}
+
+fn remove_mail(conn: &mut SqliteConnection, id: i32) -> Result<()> {
+    diesel::delete(schema::outbox::table.find(id)).execute(conn)?;
+    Ok(())
+}
+
+fn record_failure(
+    conn: &mut SqliteConnection,
+    id: i32,
+    attempts: i32,
+    error: String,
+) -> Result<()> {
+    use schema::outbox;
+
+    let status = if attempts >= MAX_ATTEMPTS {
+        log::error!("Giving up on mail {} after {} attempts", id, attempts);
+        MailStatus::Failed
+    } else {
+        MailStatus::Queued
+    };
+    let next_attempt = Local::now().naive_local() + retry_delay(attempts);
+    diesel::update(outbox::table.find(id))
+        .set((
+            outbox::status.eq(status.as_str()),
+            outbox::attempts.eq(attempts),
+            outbox::next_attempt.eq(next_attempt.format(TIMESTAMP_FORMAT).to_string()),
+            outbox::last_error.eq(error),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Time to wait after the given number of failed attempts
+fn retry_delay(attempts: i32) -> Duration {
+    // Beyond 2^16 minutes the maximum is reached anyway
+    let factor = 1 << (attempts - 1).clamp(0, 16);
+    Duration::minutes((FIRST_RETRY_MINUTES * factor).min(MAX_RETRY_MINUTES))
+}
+
+async fn send_mail(mailer: &Mailer, mail: &OutboxMail) -> Result<()> {
+    let data: serde_json::Value = serde_json::from_str(&mail.data)
+        .map_err(|e| Error::InvalidMail(format!("Invalid template data: {e}")))?;
+    let (text, html) = render(&mail.template, &data, mailer.logo.is_some())?;
+    send_rendered(mailer, mail, text, html).await
+}
+
+/// Sends the mail with the rendered text and, if there is one, HTML
+async fn send_rendered(
+    mailer: &Mailer,
+    mail: &OutboxMail,
+    text: String,
+    html: Option<String>,
+) -> Result<()> {
+    let recipient: Address = mail.recipient.parse()?;
+    // lettre can't derive the envelope from names with special characters like `<`
+    let envelope = Envelope::new(Some(mailer.sender.email.clone()), vec![recipient.clone()])?;
+    let builder = Message::builder()
+        .envelope(envelope)
+        .from(mailer.sender.clone())
+        .to(Mailbox::new(
+            Some(mail.recipient_name.clone()).filter(|name| !name.is_empty()),
+            recipient,
+        ))
+        .subject(&mail.subject);
+    let email = match (html, attachment(mail)?) {
+        (Some(html), Some(attachment)) => builder.multipart(
+            MultiPart::mixed()
+                .multipart(html_body(mailer, text, html))
+                .singlepart(attachment),
+        ),
+        (Some(html), None) => builder.multipart(html_body(mailer, text, html)),
+        (None, Some(attachment)) => builder.multipart(
+            MultiPart::mixed()
+                .singlepart(SinglePart::plain(text))
+                .singlepart(attachment),
+        ),
+        (None, None) => builder.singlepart(SinglePart::plain(text)),
+    }?;
+
+    mailer.send(email).await
+}
+
+/// Renders the text and, if there is one, the HTML template with the given name
+pub fn render(
+    template: &str,
+    email_data: &impl Serialize,
+    logo: bool,
+) -> Result<(String, Option<String>)> {
+    let mut data = serde_json::to_value(email_data).expect("Template data is always valid JSON");
+    let text = HANDLEBARS.read().render(template, &data)?;
+    let html_handlebars = HTML_HANDLEBARS.read();
+    if !html_handlebars.has_template(template) {
+        return Ok((text, None));
+    }
+    if let Some(data) = data.as_object_mut() {
+        data.insert("logo".to_string(), logo.into());
+    }
+    Ok((text, Some(html_handlebars.render(template, &data)?)))
+}
+
+fn attachment(mail: &OutboxMail) -> Result<Option<SinglePart>> {
+    let (Some(filename), Some(content_type), Some(content)) = (
+        &mail.attachment_name,
+        &mail.attachment_type,
+        &mail.attachment,
+    ) else {
+        return Ok(None);
+    };
+    let content_type = ContentType::parse(content_type)
+        .map_err(|e| Error::InvalidMail(format!("Invalid attachment type {content_type}: {e}")))?;
+    Ok(Some(
+        Attachment::new(filename.clone()).body(content.clone(), content_type),
+    ))
+}
+
+/// HTML mail with the text as fallback for clients not showing HTML.
+/// The HTML can show the configured logo with `<img src="cid:logo">`.
+fn html_body(mailer: &Mailer, text: String, html: String) -> MultiPart {
+    let Some(logo) = &mailer.logo else {
+        return MultiPart::alternative_plain_html(text, html);
+    };
+    MultiPart::alternative()
+        .singlepart(SinglePart::plain(text))
+        .multipart(
+            MultiPart::related()
+                .singlepart(SinglePart::html(html))
+                .singlepart(
+                    Attachment::new_inline("logo".to_string())
+                        .body(logo.content.clone(), logo.content_type.clone()),
+                ),
+        )
+}
+
+/// Lists the mails the outbox gave up on
+pub async fn list_failed_mails() -> Result<impl IntoResponse> {
+    let mails = tokio::task::spawn_blocking(|| -> Result<Vec<OutboxMail>> {
+        use schema::outbox;
+
+        let mut connection = crate::db::establish_connection();
+        Ok(outbox::table
+            .filter(outbox::status.eq(MailStatus::Failed.as_str()))
+            .order(outbox::id.asc())
+            .load(&mut connection)?)
+    })
+    .await
+    .unwrap()?;
+    Ok(Json(mails))
+}
+
+/// Queues a failed mail again with a fresh set of attempts. A mail that is still queued is
+/// [`Error::MailNotFound`] as well, so it isn't sent twice.
+pub async fn resend_mail(Editor(admin): Editor, Path(id): Path<i32>) -> Result<StatusCode> {
+    let updated = tokio::task::spawn_blocking(move || -> Result<usize> {
+        use schema::outbox;
+
+        let mut connection = crate::db::establish_connection();
+        Ok(diesel::update(
+            outbox::table
+                .find(id)
+                .filter(outbox::status.eq(MailStatus::Failed.as_str())),
+        )
+        .set((
+            outbox::status.eq(MailStatus::Queued.as_str()),
+            outbox::attempts.eq(0),
+            outbox::next_attempt.eq(now()),
+        ))
+        .execute(&mut connection)?)
+    })
+    .await
+    .unwrap()?;
+    if updated == 0 {
+        return Err(Error::MailNotFound);
+    }
+    log::info!("Admin {} queued mail {} again", admin.username, id);
+    wake();
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[test]
+fn test_retry_delay() {
+    assert_eq!(retry_delay(1), Duration::minutes(1));
+    assert_eq!(retry_delay(2), Duration::minutes(2));
+    assert_eq!(retry_delay(5), Duration::minutes(16));
+    assert_eq!(retry_delay(9), Duration::minutes(256));
+    assert_eq!(retry_delay(10), Duration::hours(6));
+    assert_eq!(retry_delay(100), Duration::hours(6));
+}
+
+#[tokio::test]
+async fn test_file_transport() {
+    use crate::config::{Config, MailServerConfig, MailTransport};
+
+    let directory = std::env::temp_dir().join(format!("outbox-test-{}", std::process::id()));
+    let config = Config {
+        mail_server: MailServerConfig {
+            transport: MailTransport::File,
+            mail_directory: Some(directory.clone()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mailer = Mailer::new(&config).ok().unwrap();
+    let mut conn = crate::db::test_connection();
+    enqueue_with_attachment(
+        &mut conn,
+        "Robin Hood".to_string(),
+        "robin@example.com",
+        "Anmeldung",
+        "user_mail",
+        &(),
+        Some(MailAttachment {
+            filename: "anmeldung.ics".to_string(),
+            content_type: "text/calendar; charset=utf-8; method=PUBLISH".to_string(),
+            content: "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n".to_string(),
+        }),
+    )
+    .ok()
+    .unwrap();
+
+    let mails = load_due_mails(&mut conn).ok().unwrap();
+    assert_eq!(mails.len(), 1);
+    send_rendered(&mailer, &mails[0], "Hallo Robin".to_string(), None)
+        .await
+        .ok()
+        .unwrap();
+    remove_mail(&mut conn, mails[0].id).ok().unwrap();
+    assert!(load_due_mails(&mut conn).ok().unwrap().is_empty());
+
+    let files: Vec<_> = std::fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(files.len(), 1);
+    let content = std::fs::read_to_string(&files[0]).unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert!(content.contains("To: \"Robin Hood\" <robin@example.com>"));
+    assert!(content.contains("Subject: Anmeldung"));
+    assert!(content.contains("Hallo Robin"));
+    assert!(content.contains("filename=\"anmeldung.ics\""));
+}
@@ -0,0 +1,179 @@
+//! Self-service page for archers to view, change or withdraw their registration.
+//!
+//! The confirmation mail contains a link with a token `<bib>.<signature>`. The signature is an
+//! HMAC of the bib with the `link_secret` from the config, so tokens can't be guessed.
+
+use crate::{archer, config::EventConfig, error::*, event, outbox, repository, CONFIG};
+use axum::{extract::Path, http::StatusCode, Json};
+use common::archer::{Archer, Registration, RegistrationError};
+use diesel::prelude::*;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn signer(secret: &str, bib: i32) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("registration:{bib}").as_bytes());
+    mac
+}
+
+pub fn token(bib: i32) -> String {
+    sign(&CONFIG.read().link_secret, bib)
+}
+
+fn sign(secret: &str, bib: i32) -> String {
+    let signature = signer(secret, bib)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!("{bib}.{signature}")
+}
+
+/// Returns the bib of a valid token
+fn verify_token(token: &str) -> Result<i32> {
+    verify(&CONFIG.read().link_secret, token)
+}
+
+fn verify(secret: &str, token: &str) -> Result<i32> {
+    let (bib, signature) = token.split_once('.').ok_or(Error::InvalidLink)?;
+    let bib = bib.parse().map_err(|_| Error::InvalidLink)?;
+    if signature.len() % 2 != 0 || !signature.is_ascii() {
+        return Err(Error::InvalidLink);
+    }
+    let signature = (0..signature.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&signature[i..i + 2], 16))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|_| Error::InvalidLink)?;
+    signer(secret, bib)
+        .verify_slice(&signature)
+        .map_err(|_| Error::InvalidLink)?;
+    Ok(bib)
+}
+
+/// Link to the self-service page of the registration
+pub fn manage_url(bib: i32) -> String {
+    format!(
+        "{}/?manage={}",
+        CONFIG.read().public_url.trim_end_matches('/'),
+        token(bib)
+    )
+}
+
+pub async fn get_registration(Path(token): Path<String>) -> Result<Json<Registration>> {
+    let bib = verify_token(&token)?;
+    let registration = tokio::task::spawn_blocking(move || {
+        repository::load(&mut crate::db::establish_connection(), bib).map(Registration::from)
+    })
+    .await
+    .unwrap()?;
+    Ok(Json(registration))
+}
+
+pub async fn update_registration(
+    Path(token): Path<String>,
+    Json(payload): Json<Archer>,
+) -> Result<Json<Registration>> {
+    let bib = verify_token(&token)?;
+    let registration = tokio::task::spawn_blocking(move || {
+        repository::load(&mut crate::db::establish_connection(), bib).map(Registration::from)
+    })
+    .await
+    .unwrap()?;
+    let event = event::find_event(registration.event)?;
+    event::check_registration_window(&event)?;
+    let mut errors = payload.validate(&event.info()).err().unwrap_or_default();
+    // Mails may only go to confirmed addresses, see `verification`
+    if payload.mail.as_str() != registration.mail {
+        errors.push(RegistrationError::MailChanged {
+            mail: registration.mail,
+        });
+    }
+    if !errors.is_empty() {
+        return Err(errors.into());
+    }
+
+    let registration =
+        tokio::task::spawn_blocking(move || archer::update_archer(bib, payload, &event))
+            .await
+            .unwrap()?;
+    outbox::wake();
+    log::info!("Archer {} changed the registration", bib);
+    Ok(Json(registration))
+}
+
+pub async fn withdraw_registration(Path(token): Path<String>) -> Result<StatusCode> {
+    let bib = verify_token(&token)?;
+    tokio::task::spawn_blocking(move || {
+        crate::db::establish_connection().immediate_transaction(|conn| {
+            let registration: Registration = repository::load(conn, bib)?.into();
+            archer::remove_archer(conn, bib)?;
+            queue_withdrawal_mail(conn, &registration, bib)
+        })
+    })
+    .await
+    .unwrap()?;
+    outbox::wake();
+    log::info!("Archer {} withdrew the registration", bib);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn queue_withdrawal_mail(
+    conn: &mut SqliteConnection,
+    registration: &Registration,
+    bib: i32,
+) -> Result<()> {
+    let Ok(event) = event::find_event(registration.event) else {
+        // Nothing to tell about events removed from the config
+        return Ok(());
+    };
+    let email_data = withdrawal_mail_data(&event, registration);
+    let subject = {
+        let config = CONFIG.read();
+        config
+            .mail_message
+            .withdrawal_subject
+            .clone()
+            .unwrap_or_else(|| config.mail_message.subject.clone())
+    };
+    // Removes the entry sent with the confirmation, which archers on the waiting list didn't get
+    let calendar = (!registration.waiting_list)
+        .then(|| archer::calendar_attachment(&event, registration.session, bib, true));
+    outbox::enqueue_with_attachment(
+        conn,
+        format!("{} {}", registration.first_name, registration.last_name),
+        &registration.mail,
+        &subject,
+        "withdrawal_mail",
+        &email_data,
+        calendar,
+    )
+}
+
+pub fn withdrawal_mail_data(
+    event: &EventConfig,
+    registration: &Registration,
+) -> BTreeMap<&'static str, String> {
+    BTreeMap::from([
+        ("event_name", event.name.clone()),
+        ("event_date", event.date.format("%d.%m.%Y").to_string()),
+        ("event_venue", event.venue.clone()),
+        ("first_name", registration.first_name.clone()),
+        ("last_name", registration.last_name.clone()),
+    ])
+}
+
+#[test]
+fn test_token() {
+    let token = sign("geheim", 42);
+    assert_eq!(verify("geheim", &token).ok(), Some(42));
+    assert!(verify("anders", &token).is_err());
+    assert!(verify("geheim", &token.replacen("42", "43", 1)).is_err());
+    assert!(verify("geheim", "42").is_err());
+    assert!(verify("geheim", "42.zz").is_err());
+}
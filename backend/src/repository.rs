@@ -0,0 +1,598 @@
+//! Typed access to the stored registrations.
+//!
+//! A registration is spread over the Ianseo table `archers` and our `archer_additions`, with
+//! classes, target faces and dates of birth stored as text. This module converts between these
+//! rows and the types of `common`, so the handlers don't need to know the columns.
+//! The admin lists and exports also show archers entered in Ianseo without registration.
+
+use crate::{
+    archer::division,
+    error::*,
+    models::{
+        self, ArcherAdditions, InsertableArcher, RegisteredArcher, Status, DATE_OF_BIRTH_FORMAT,
+        TIMESTAMP_FORMAT,
+    },
+    schema::{archer_additions, archers},
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use common::{
+    archer::{Archer, Registration},
+    class::Class,
+    gender::Gender,
+    target_face::TargetFace,
+};
+use diesel::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// A registration as stored. Registrations from older versions lack some of the data,
+/// so everything that can't be read back is optional.
+#[derive(Debug)]
+pub struct StoredArcher {
+    pub bib: i32,
+    pub event: i32,
+    pub status: Status,
+    pub first_name: String,
+    pub last_name: String,
+    pub mail: String,
+    pub comment: String,
+    pub date_of_birth: Option<NaiveDate>,
+    pub gender: Option<Gender>,
+    pub class: Option<Class>,
+    pub target_face: Option<TargetFace>,
+    pub session: i32,
+}
+
+#[derive(Queryable)]
+struct ArcherRow {
+    bib: i32,
+    session: i32,
+    class: String,
+    target: String,
+    last_name: String,
+    first_name: String,
+    gender: Option<i32>,
+    date_of_birth: String,
+    email: Option<String>,
+    comment: Option<String>,
+    event: i32,
+    status: String,
+}
+
+type RowColumns = (
+    archers::bib,
+    archers::session,
+    archers::class,
+    archers::target,
+    archers::last_name,
+    archers::first_name,
+    archers::gender,
+    archers::date_of_birth,
+    archer_additions::email,
+    archer_additions::comment,
+    archer_additions::event,
+    archer_additions::status,
+);
+
+const ROW_COLUMNS: RowColumns = (
+    archers::bib,
+    archers::session,
+    archers::class,
+    archers::target,
+    archers::last_name,
+    archers::first_name,
+    archers::gender,
+    archers::date_of_birth,
+    archer_additions::email,
+    archer_additions::comment,
+    archer_additions::event,
+    archer_additions::status,
+);
+
+impl TryFrom<ArcherRow> for StoredArcher {
+    type Error = Error;
+
+    fn try_from(row: ArcherRow) -> Result<Self> {
+        let status = Status::parse(&row.status).ok_or_else(|| {
+            Error::DBError(diesel::result::Error::DeserializationError(
+                format!("Unknown status {} of archer {}", row.status, row.bib).into(),
+            ))
+        })?;
+        Ok(StoredArcher {
+            bib: row.bib,
+            event: row.event,
+            status,
+            first_name: row.first_name,
+            last_name: row.last_name,
+            mail: row.email.unwrap_or_default(),
+            comment: row.comment.unwrap_or_default(),
+            date_of_birth: NaiveDate::parse_from_str(&row.date_of_birth, DATE_OF_BIRTH_FORMAT).ok(),
+            gender: row.gender.and_then(Gender::from_ianseo_code),
+            class: row.class.parse().ok(),
+            target_face: row.target.parse().ok(),
+            session: row.session,
+        })
+    }
+}
+
+impl StoredArcher {
+    /// The registration as [`Archer`], `None` if some of the data is unknown.
+    /// It isn't validated again, the rules may have changed since it was stored.
+    pub fn archer(&self) -> Option<Archer> {
+        Some(Archer::new_unchecked(
+            self.first_name.clone(),
+            self.last_name.clone(),
+            &self.mail,
+            self.date_of_birth?,
+            self.gender?,
+            self.class.clone()?,
+            self.target_face?,
+            self.session,
+            self.comment.clone(),
+        ))
+    }
+}
+
+impl From<StoredArcher> for Registration {
+    fn from(stored: StoredArcher) -> Self {
+        Registration {
+            event: stored.event,
+            waiting_list: stored.status == Status::Waiting,
+            first_name: stored.first_name,
+            last_name: stored.last_name,
+            mail: stored.mail,
+            comment: stored.comment,
+            date_of_birth: stored.date_of_birth,
+            gender: stored.gender,
+            class: stored.class,
+            target_face: stored.target_face,
+            session: stored.session,
+        }
+    }
+}
+
+/// Loads the registration with the given bib. Archers entered in Ianseo aren't registrations
+/// and give [`Error::NotFound`] as well.
+pub fn load(conn: &mut SqliteConnection, bib: i32) -> Result<StoredArcher> {
+    archers::table
+        .inner_join(archer_additions::table.on(archer_additions::bib.eq(archers::bib)))
+        .filter(archers::bib.eq(bib))
+        .select(ROW_COLUMNS)
+        .first::<ArcherRow>(conn)
+        .optional()?
+        .ok_or(Error::NotFound)?
+        .try_into()
+}
+
+/// Loads the registrations, of one event or all, ordered by bib
+pub fn list(conn: &mut SqliteConnection, event: Option<i32>) -> Result<Vec<StoredArcher>> {
+    let mut select = archers::table
+        .inner_join(archer_additions::table.on(archer_additions::bib.eq(archers::bib)))
+        .select(ROW_COLUMNS)
+        .order(archers::bib.asc())
+        .into_boxed();
+    if let Some(event) = event {
+        select = select.filter(archer_additions::event.eq(event));
+    }
+    select
+        .load::<ArcherRow>(conn)?
+        .into_iter()
+        .map(StoredArcher::try_from)
+        .collect()
+}
+
+/// Filters of [`list_entries`], `None` matches everything
+#[derive(Debug, Default)]
+pub struct EntryFilter {
+    pub event: Option<i32>,
+    pub class: Option<String>,
+    pub division: Option<String>,
+    pub session: Option<i32>,
+    pub target: Option<String>,
+    pub status: Option<String>,
+    /// Leaves out unconfirmed registrations. Archers entered in Ianseo stay.
+    pub without_pending: bool,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Bib,
+    Name,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Page {
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// Loads the archers for the admin list and the exports, also archers entered in Ianseo
+pub fn list_entries(
+    conn: &mut SqliteConnection,
+    filter: EntryFilter,
+    sort: SortBy,
+    page: Option<Page>,
+) -> Result<Vec<RegisteredArcher>> {
+    let mut select = archers::table
+        .left_join(archer_additions::table.on(archer_additions::bib.eq(archers::bib)))
+        .select((
+            archers::bib,
+            archers::session,
+            archers::division,
+            archers::class,
+            archers::target,
+            archers::last_name,
+            archers::first_name,
+            archers::date_of_birth,
+            archer_additions::email.nullable(),
+            archer_additions::comment.nullable(),
+            archer_additions::event.nullable(),
+            archer_additions::status.nullable(),
+        ))
+        .into_boxed();
+    if filter.without_pending {
+        select = select.filter(
+            archer_additions::status
+                .nullable()
+                .is_null()
+                .or(archer_additions::status
+                    .nullable()
+                    .ne(Status::Pending.as_str())),
+        );
+    }
+    if let Some(event) = filter.event {
+        select = select.filter(archer_additions::event.eq(event));
+    }
+    if let Some(class) = filter.class {
+        select = select.filter(archers::class.eq(class));
+    }
+    if let Some(division) = filter.division {
+        select = select.filter(archers::division.eq(division));
+    }
+    if let Some(session) = filter.session {
+        select = select.filter(archers::session.eq(session));
+    }
+    if let Some(target) = filter.target {
+        select = select.filter(archers::target.eq(target));
+    }
+    if let Some(status) = filter.status {
+        select = select.filter(archer_additions::status.eq(status));
+    }
+    select = match sort {
+        SortBy::Bib => select.order(archers::bib.asc()),
+        SortBy::Name => select.order((
+            archers::last_name.asc(),
+            archers::first_name.asc(),
+            archers::bib.asc(),
+        )),
+    };
+    if let Some(page) = page {
+        select = select.limit(page.limit).offset(page.offset);
+    }
+    Ok(select.load(conn)?)
+}
+
+/// Ianseo rows of the archers with a place, including archers entered in Ianseo, ordered by bib
+pub fn list_ianseo_rows(
+    conn: &mut SqliteConnection,
+    event: Option<i32>,
+) -> Result<Vec<models::Archer>> {
+    let mut select = archers::table
+        .filter(
+            archers::bib.ne_all(
+                archer_additions::table
+                    .filter(archer_additions::status.ne(Status::Registered.as_str()))
+                    .select(archer_additions::bib),
+            ),
+        )
+        .order(archers::bib.asc())
+        .into_boxed();
+    if let Some(event) = event {
+        select = select.filter(
+            archers::bib.eq_any(
+                archer_additions::table
+                    .filter(archer_additions::event.eq(event))
+                    .select(archer_additions::bib),
+            ),
+        );
+    }
+    Ok(select.load(conn)?)
+}
+
+/// Positions of all waiting archers by bib, counted separately for each event and session
+pub fn waitlist_positions(conn: &mut SqliteConnection) -> Result<HashMap<i32, usize>> {
+    let waiting: Vec<(i32, i32, i32)> = archers::table
+        .inner_join(archer_additions::table.on(archer_additions::bib.eq(archers::bib)))
+        .filter(archer_additions::status.eq(Status::Waiting.as_str()))
+        .order(archers::bib.asc())
+        .select((archers::bib, archer_additions::event, archers::session))
+        .load(conn)?;
+
+    let mut counts = HashMap::new();
+    Ok(waiting
+        .into_iter()
+        .map(|(bib, event, session)| {
+            let count = counts.entry((event, session)).or_insert(0);
+            *count += 1;
+            (bib, *count)
+        })
+        .collect())
+}
+
+/// Number of archers with a place at the event, or only in the given session
+pub fn count_registered(
+    conn: &mut SqliteConnection,
+    event: i32,
+    session: Option<i32>,
+) -> Result<i64> {
+    let mut count = archers::table
+        .inner_join(archer_additions::table.on(archer_additions::bib.eq(archers::bib)))
+        .filter(archer_additions::event.eq(event))
+        .filter(archer_additions::status.eq(Status::Registered.as_str()))
+        .count()
+        .into_boxed();
+    if let Some(session) = session {
+        count = count.filter(archers::session.eq(session));
+    }
+    Ok(count.get_result(conn)?)
+}
+
+/// Bib and expiry time of the registration with the verification token
+pub fn find_verification(
+    conn: &mut SqliteConnection,
+    token: &str,
+) -> Result<Option<(i32, Option<NaiveDateTime>)>> {
+    let found: Option<(i32, Option<String>)> = archer_additions::table
+        .filter(archer_additions::verification_token.eq(token))
+        .select((
+            archer_additions::bib,
+            archer_additions::verification_expires,
+        ))
+        .first(conn)
+        .optional()?;
+    Ok(found.map(|(bib, expires)| {
+        let expires = expires
+            .and_then(|expires| NaiveDateTime::parse_from_str(&expires, TIMESTAMP_FORMAT).ok());
+        (bib, expires)
+    }))
+}
+
+/// Sets the status of the registration. Confirmed registrations don't expire anymore.
+pub fn set_status(conn: &mut SqliteConnection, bib: i32, status: Status) -> Result<()> {
+    let registration = archer_additions::table.find(bib);
+    let updated = if status == Status::Pending {
+        diesel::update(registration)
+            .set(archer_additions::status.eq(status.as_str()))
+            .execute(conn)?
+    } else {
+        diesel::update(registration)
+            .set((
+                archer_additions::status.eq(status.as_str()),
+                archer_additions::verification_expires.eq(None::<String>),
+            ))
+            .execute(conn)?
+    };
+    if updated == 0 {
+        return Err(Error::NotFound);
+    }
+    Ok(())
+}
+
+/// Stores the archer as [`Status::Pending`] until the email address is confirmed with the token.
+/// Returns the bib.
+pub fn insert(
+    conn: &mut SqliteConnection,
+    archer: &Archer,
+    event: i32,
+    verification_token: &str,
+    verification_expires: NaiveDateTime,
+) -> Result<i32> {
+    let bib: i32 = diesel::insert_into(archers::table)
+        .values(InsertableArcher {
+            session: archer.session(),
+            division: division(archer.class()).to_string(),
+            class: archer.class().code().to_string(),
+            target: archer.target_face().code().to_string(),
+            individual_qualification: 1,
+            team_qualification: 1,
+            individual_final: 1,
+            team_final: 1,
+            mixed_team_final: 1,
+            last_name: archer.last_name.clone(),
+            first_name: archer.first_name.clone(),
+            gender: Some(archer.gender().ianseo_code()),
+            date_of_birth: archer
+                .date_of_birth()
+                .format(DATE_OF_BIRTH_FORMAT)
+                .to_string(),
+            country_code: "PSV".to_string(),
+            country_name: "PSV München".to_string(),
+            ..Default::default()
+        })
+        .returning(archers::bib)
+        .get_result(conn)?;
+
+    diesel::insert_into(archer_additions::table)
+        .values(ArcherAdditions {
+            bib,
+            email: archer.mail.as_str().to_owned(),
+            comment: archer.comment.clone(),
+            event,
+            status: Status::Pending.as_str().to_string(),
+            verification_token: Some(verification_token.to_string()),
+            verification_expires: Some(verification_expires.format(TIMESTAMP_FORMAT).to_string()),
+            organizer_notified: false,
+        })
+        .execute(conn)?;
+    Ok(bib)
+}
+
+/// Overwrites the data of the registration. The event and the status stay unchanged.
+pub fn update(conn: &mut SqliteConnection, bib: i32, archer: &Archer) -> Result<()> {
+    let updated = diesel::update(archers::table.find(bib))
+        .set((
+            archers::session.eq(archer.session()),
+            archers::division.eq(division(archer.class())),
+            archers::class.eq(archer.class().code()),
+            archers::target.eq(archer.target_face().code()),
+            archers::last_name.eq(&archer.last_name),
+            archers::first_name.eq(&archer.first_name),
+            archers::gender.eq(archer.gender().ianseo_code()),
+            archers::date_of_birth.eq(archer
+                .date_of_birth()
+                .format(DATE_OF_BIRTH_FORMAT)
+                .to_string()),
+        ))
+        .execute(conn)?;
+    if updated == 0 {
+        return Err(Error::NotFound);
+    }
+    diesel::update(archer_additions::table.find(bib))
+        .set((
+            archer_additions::email.eq(archer.mail.as_str()),
+            archer_additions::comment.eq(&archer.comment),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Deletes the archer, also archers entered in Ianseo without registration
+pub fn delete(conn: &mut SqliteConnection, bib: i32) -> Result<()> {
+    diesel::delete(archer_additions::table.find(bib)).execute(conn)?;
+    let deleted = diesel::delete(archers::table.find(bib)).execute(conn)?;
+    if deleted == 0 {
+        return Err(Error::NotFound);
+    }
+    Ok(())
+}
+
+/// Robin Hood in R10 with the given mail address and session
+#[cfg(test)]
+pub fn test_archer(mail: &str, session: i32) -> Archer {
+    Archer::new_unchecked(
+        "Robin".to_string(),
+        "Hood".to_string(),
+        mail,
+        NaiveDate::from_ymd_opt(1990, 6, 1).unwrap(),
+        Gender::Male,
+        Class::new("R10"),
+        TargetFace::Spot,
+        session,
+        String::new(),
+    )
+}
+
+#[test]
+fn test_stored_archer() {
+    let row = ArcherRow {
+        bib: 7,
+        session: 2,
+        class: "R10".to_string(),
+        target: "122cm".to_string(),
+        last_name: "Hood".to_string(),
+        first_name: "Robin".to_string(),
+        gender: Some(1),
+        date_of_birth: "2000-01-01".to_string(),
+        email: Some("robin.hood@example.com".to_string()),
+        comment: None,
+        event: 1,
+        status: "waiting".to_string(),
+    };
+    let stored = StoredArcher::try_from(row).ok().unwrap();
+    assert_eq!(stored.status, Status::Waiting);
+    let archer = stored.archer().unwrap();
+    assert_eq!(archer.class(), &Class::new("R10"));
+    assert_eq!(archer.target_face(), TargetFace::Cm122);
+    assert_eq!(archer.gender(), Gender::Female);
+    assert_eq!(archer.session(), 2);
+
+    // Registrations from before the date of birth was stored
+    let stored = StoredArcher {
+        date_of_birth: None,
+        ..stored
+    };
+    assert!(stored.archer().is_none());
+    let registration = Registration::from(stored);
+    assert!(registration.waiting_list);
+    assert_eq!(registration.class, Some(Class::new("R10")));
+}
+
+#[test]
+fn test_gender() {
+    let mut conn = crate::db::test_connection();
+    let expires = chrono::Local::now().naive_local();
+    let marian = Archer::new_unchecked(
+        "Marian".to_string(),
+        "Hood".to_string(),
+        "marian@example.com",
+        NaiveDate::from_ymd_opt(1992, 3, 1).unwrap(),
+        Gender::Female,
+        Class::new("R11"),
+        TargetFace::Spot,
+        1,
+        String::new(),
+    );
+    let bib = insert(&mut conn, &marian, 1, "token", expires)
+        .ok()
+        .unwrap();
+    assert_eq!(
+        load(&mut conn, bib).ok().unwrap().gender,
+        Some(Gender::Female)
+    );
+
+    // Ianseo reads the gender code from its own table
+    set_status(&mut conn, bib, Status::Registered).ok().unwrap();
+    let rows = list_ianseo_rows(&mut conn, Some(1)).ok().unwrap();
+    assert_eq!(rows[0].gender, Some(Gender::Female.ianseo_code()));
+
+    update(&mut conn, bib, &test_archer("marian@example.com", 1))
+        .ok()
+        .unwrap();
+    let rows = list_ianseo_rows(&mut conn, Some(1)).ok().unwrap();
+    assert_eq!(rows[0].gender, Some(Gender::Male.ianseo_code()));
+}
+
+#[test]
+fn test_date_of_birth() {
+    use diesel_migrations::MigrationHarness;
+
+    // Archers entered in Ianseo before the migration have German dates
+    let mut conn = SqliteConnection::establish(":memory:").unwrap();
+    for migration in conn.pending_migrations(crate::MIGRATIONS).unwrap() {
+        if migration.name().to_string().ends_with("_date_of_birth") {
+            diesel::sql_query(
+                r#"INSERT INTO archers (session, division, class, target,
+                "individual qualification", "team qualification", "individual final",
+                "team final", "mixed team final", "last name", "first name",
+                "country code", "country name", "date of birth")
+                VALUES (1, 'R', 'RM', '', 1, 1, 1, 1, 1, 'Tuck', 'Bruder', 'PSV', 'PSV', '24.12.1960')"#,
+            )
+            .execute(&mut conn)
+            .unwrap();
+        }
+        conn.run_migration(&migration).unwrap();
+    }
+
+    let bib = insert(
+        &mut conn,
+        &test_archer("robin@example.com", 1),
+        1,
+        "token",
+        chrono::Local::now().naive_local(),
+    )
+    .ok()
+    .unwrap();
+    assert_eq!(
+        load(&mut conn, bib).ok().unwrap().date_of_birth,
+        NaiveDate::from_ymd_opt(1990, 6, 1)
+    );
+    let dates: Vec<_> = list_entries(&mut conn, EntryFilter::default(), SortBy::Bib, None)
+        .ok()
+        .unwrap()
+        .into_iter()
+        .map(|archer| archer.date_of_birth)
+        .collect();
+    assert_eq!(dates, ["1960-12-24", "1990-06-01"]);
+}
@@ -5,6 +5,11 @@ diesel::table! {
         bib -> Integer,
         email -> Nullable<Text>,
         comment -> Nullable<Text>,
+        event -> Integer,
+        status -> Text,
+        verification_token -> Nullable<Text>,
+        verification_expires -> Nullable<Text>,
+        organizer_notified -> Bool,
     }
 }
 
@@ -48,4 +53,32 @@ diesel::table! {
     }
 }
 
-diesel::allow_tables_to_appear_in_same_query!(archer_additions, archers,);
+diesel::table! {
+    events (id) {
+        id -> Integer,
+        name -> Text,
+        date -> Text,
+        venue -> Text,
+    }
+}
+
+diesel::table! {
+    outbox (id) {
+        id -> Integer,
+        recipient_name -> Text,
+        recipient -> Text,
+        subject -> Text,
+        template -> Text,
+        data -> Text,
+        status -> Text,
+        attempts -> Integer,
+        next_attempt -> Text,
+        last_error -> Nullable<Text>,
+        created -> Text,
+        attachment_name -> Nullable<Text>,
+        attachment_type -> Nullable<Text>,
+        attachment -> Nullable<Text>,
+    }
+}
+
+diesel::allow_tables_to_appear_in_same_query!(archer_additions, archers, events, outbox,);
@@ -0,0 +1,247 @@
+//! Export of the participant list as CSV or XLSX for the organisers.
+
+use crate::{
+    auth::AdminSession,
+    error::*,
+    models::{RegisteredArcher, Status},
+    repository::{self, EntryFilter, SortBy},
+};
+use axum::{
+    extract::Query,
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::IntoResponse,
+    Extension,
+};
+use rust_xlsxwriter::{Format, Workbook};
+use serde::Deserialize;
+use strum::IntoEnumIterator;
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    Csv,
+    Xlsx,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ExportQuery {
+    format: FileFormat,
+    /// Comma separated list of [`Column`]s. All columns if omitted
+    columns: Option<String>,
+    event: Option<i32>,
+    division: Option<String>,
+    session: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumIter)]
+pub enum Column {
+    Bib,
+    Session,
+    Division,
+    LastName,
+    FirstName,
+    DateOfBirth,
+    Email,
+    Class,
+    TargetFace,
+    Comment,
+    Status,
+}
+
+impl Column {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name.trim() {
+            "bib" => Self::Bib,
+            "session" => Self::Session,
+            "division" => Self::Division,
+            "last_name" => Self::LastName,
+            "first_name" => Self::FirstName,
+            "date_of_birth" => Self::DateOfBirth,
+            "email" => Self::Email,
+            "class" => Self::Class,
+            "target_face" => Self::TargetFace,
+            "comment" => Self::Comment,
+            "status" => Self::Status,
+            _ => return None,
+        })
+    }
+    fn header(&self) -> &'static str {
+        match self {
+            Column::Bib => "Startnummer",
+            Column::Session => "Durchgang",
+            Column::Division => "Bogenart",
+            Column::LastName => "Nachname",
+            Column::FirstName => "Vorname",
+            Column::DateOfBirth => "Geburtsdatum",
+            Column::Email => "Email",
+            Column::Class => "Klasse",
+            Column::TargetFace => "Auflage",
+            Column::Comment => "Kommentar",
+            Column::Status => "Status",
+        }
+    }
+    fn value(&self, archer: &RegisteredArcher) -> String {
+        match self {
+            Column::Bib => archer.bib.to_string(),
+            Column::Session => archer.session.to_string(),
+            Column::Division => archer.division.clone(),
+            Column::LastName => archer.last_name.clone(),
+            Column::FirstName => archer.first_name.clone(),
+            Column::DateOfBirth => archer
+                .date_of_birth()
+                .map(|dob| dob.format("%d.%m.%Y").to_string())
+                .unwrap_or_else(|| archer.date_of_birth.clone()),
+            Column::Email => archer.email.clone().unwrap_or_default(),
+            Column::Class => archer
+                .class()
+                .map(|cls| cls.name())
+                .unwrap_or_else(|| archer.class.clone()),
+            Column::TargetFace => archer
+                .target_face()
+                .map(|tf| tf.to_string())
+                .unwrap_or_else(|| archer.target.clone()),
+            Column::Comment => archer.comment.clone().unwrap_or_default(),
+            Column::Status => status_label(archer.status.as_deref()),
+        }
+    }
+}
+
+/// Unknown values are shown as they are rather than as some other status
+fn status_label(status: Option<&str>) -> String {
+    let Some(status) = status else {
+        // Entered in Ianseo directly
+        return "Ohne Anmeldung".to_string();
+    };
+    match Status::parse(status) {
+        Some(Status::Pending) => "Unbestätigt",
+        Some(Status::Registered) => "Angemeldet",
+        Some(Status::Waiting) => "Warteliste",
+        None => status,
+    }
+    .to_string()
+}
+
+pub async fn export(
+    Extension(admin): Extension<AdminSession>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse> {
+    let columns = match &query.columns {
+        Some(names) => names
+            .split(',')
+            .map(|name| Column::parse(name).ok_or_else(|| Error::UnknownColumn(name.to_string())))
+            .collect::<Result<Vec<_>>>()?,
+        None => Column::iter().collect(),
+    };
+    let format = query.format;
+    let archers = tokio::task::spawn_blocking(move || load_archers(query))
+        .await
+        .unwrap()?;
+    log::info!(
+        "Admin {} exported {} archers as {:?}",
+        admin.username,
+        archers.len(),
+        format
+    );
+
+    Ok(match format {
+        FileFormat::Csv => (
+            [
+                (CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    CONTENT_DISPOSITION,
+                    "attachment; filename=\"teilnehmer.csv\"",
+                ),
+            ],
+            write_csv(&archers, &columns),
+        ),
+        FileFormat::Xlsx => (
+            [
+                (
+                    CONTENT_TYPE,
+                    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+                ),
+                (
+                    CONTENT_DISPOSITION,
+                    "attachment; filename=\"teilnehmer.xlsx\"",
+                ),
+            ],
+            write_xlsx(&archers, &columns)?,
+        ),
+    })
+}
+
+fn load_archers(query: ExportQuery) -> Result<Vec<RegisteredArcher>> {
+    let filter = EntryFilter {
+        event: query.event,
+        division: query.division,
+        session: query.session,
+        // Unconfirmed registrations don't count yet
+        without_pending: true,
+        ..Default::default()
+    };
+    let mut connection = crate::db::establish_connection();
+    repository::list_entries(&mut connection, filter, SortBy::Bib, None)
+}
+
+fn write_csv(archers: &[RegisteredArcher], columns: &[Column]) -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(columns.iter().map(Column::header))
+        .expect("Writing to a Vec can't fail");
+    for archer in archers {
+        writer
+            .write_record(columns.iter().map(|column| csv_text(column.value(archer))))
+            .expect("Writing to a Vec can't fail");
+    }
+    writer.into_inner().expect("Writing to a Vec can't fail")
+}
+
+/// Names and comments come from the archers. Excel and LibreOffice run cells starting like a
+/// formula as one, unless a `'` marks them as text.
+fn csv_text(value: String) -> String {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{value}")
+    } else {
+        value
+    }
+}
+
+fn write_xlsx(archers: &[RegisteredArcher], columns: &[Column]) -> Result<Vec<u8>> {
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Teilnehmer")?;
+    let bold = Format::new().set_bold();
+    // String cells are never run as formulas, whatever they start with
+    for (col, column) in (0..).zip(columns) {
+        sheet.write_string_with_format(0, col, column.header(), &bold)?;
+        for (row, archer) in (1..).zip(archers) {
+            match column {
+                Column::Bib => sheet.write_number(row, col, archer.bib)?,
+                Column::Session => sheet.write_number(row, col, archer.session)?,
+                _ => sheet.write_string(row, col, column.value(archer))?,
+            };
+        }
+    }
+    sheet.autofit();
+    Ok(workbook.save_to_buffer()?)
+}
+
+#[test]
+fn test_csv_text() {
+    assert_eq!(csv_text("Robin".to_string()), "Robin");
+    assert_eq!(
+        csv_text("=HYPERLINK(\"http://example.com\")".to_string()),
+        "'=HYPERLINK(\"http://example.com\")"
+    );
+    assert_eq!(csv_text("@SUM(A1)".to_string()), "'@SUM(A1)");
+    assert_eq!(csv_text("-1+1".to_string()), "'-1+1");
+}
+
+#[test]
+fn test_status_label() {
+    assert_eq!(status_label(Some("registered")), "Angemeldet");
+    assert_eq!(status_label(Some("waiting")), "Warteliste");
+    assert_eq!(status_label(Some("pending")), "Unbestätigt");
+    assert_eq!(status_label(Some("cancelled")), "cancelled");
+    assert_eq!(status_label(None), "Ohne Anmeldung");
+}
@@ -0,0 +1,155 @@
+//! Delivery of the mails built by the [`outbox`](crate::outbox) with the transport
+//! configured in `mail_server`. Besides SMTP, mails can be written to files or the log,
+//! so development setups and tests don't need a mail server.
+//!
+//! The [`Mailer`] is built once at startup, so a broken mail config stops the server right away
+//! and SMTP connections are pooled instead of opened for every mail.
+
+use crate::{
+    config::{Config, MailTransport},
+    error::*,
+};
+use chrono::Local;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::{authentication::Credentials, PoolConfig};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::path::PathBuf;
+
+pub struct Mailer {
+    transport: Transport,
+    pub sender: Mailbox,
+    /// Image for HTML mails, see `logo` in the config
+    pub logo: Option<Logo>,
+}
+
+enum Transport {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    File(PathBuf),
+    Log,
+}
+
+pub struct Logo {
+    pub content: Vec<u8>,
+    pub content_type: ContentType,
+}
+
+impl Mailer {
+    /// Checks the mail settings of the config and prepares the transport
+    pub fn new(config: &Config) -> Result<Self> {
+        let server = &config.mail_server;
+        let transport = match server.transport {
+            MailTransport::Relay | MailTransport::Smtp if server.smtp_server.is_empty() => {
+                return Err(Error::MailConfigError(
+                    "smtp_server is required for SMTP transports".to_string(),
+                ))
+            }
+            MailTransport::Relay => {
+                let mut builder = AsyncSmtpTransport::<Tokio1Executor>::relay(&server.smtp_server)?
+                    .credentials(Credentials::new(
+                        server.smtp_username.clone(),
+                        server.smtp_password.clone(),
+                    ))
+                    .pool_config(PoolConfig::new());
+                if let Some(port) = server.smtp_port {
+                    builder = builder.port(port);
+                }
+                Transport::Smtp(builder.build())
+            }
+            MailTransport::Smtp => {
+                let mut builder =
+                    AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&server.smtp_server)
+                        .port(server.smtp_port.unwrap_or(25))
+                        .pool_config(PoolConfig::new());
+                if !server.smtp_username.is_empty() {
+                    builder = builder.credentials(Credentials::new(
+                        server.smtp_username.clone(),
+                        server.smtp_password.clone(),
+                    ));
+                }
+                Transport::Smtp(builder.build())
+            }
+            MailTransport::File => {
+                let directory = server.mail_directory.clone().ok_or_else(|| {
+                    Error::MailConfigError(
+                        "mail_directory is required for the file transport".to_string(),
+                    )
+                })?;
+                std::fs::create_dir_all(&directory).map_err(|e| {
+                    Error::MailConfigError(format!("Couldn't create {directory:?}: {e}"))
+                })?;
+                Transport::File(directory)
+            }
+            MailTransport::Log => Transport::Log,
+        };
+
+        let message = &config.mail_message;
+        let sender = Mailbox::new(
+            Some(message.sender_name.clone()),
+            message.sender_address.as_str().parse()?,
+        );
+        let logo = match &message.logo {
+            Some(path) => Some(Logo {
+                content: std::fs::read(path).map_err(|e| {
+                    Error::MailConfigError(format!("Couldn't read logo {path:?}: {e}"))
+                })?,
+                content_type: logo_content_type(path),
+            }),
+            None => None,
+        };
+        Ok(Mailer {
+            transport,
+            sender,
+            logo,
+        })
+    }
+
+    /// Connects to the SMTP server and logs in. Other transports need no connection.
+    pub async fn test_connection(&self) -> Result<()> {
+        match &self.transport {
+            Transport::Smtp(transport) => {
+                if !transport.test_connection().await? {
+                    return Err(Error::MailConfigError(
+                        "SMTP server didn't accept the connection".to_string(),
+                    ));
+                }
+            }
+            Transport::File(_) | Transport::Log => {}
+        }
+        Ok(())
+    }
+
+    pub async fn send(&self, email: Message) -> Result<()> {
+        match &self.transport {
+            Transport::Smtp(transport) => {
+                transport.send(email).await?;
+            }
+            Transport::File(directory) => {
+                // The outbox sends one mail after the other, so the time is unique
+                let path =
+                    directory.join(format!("{}.eml", Local::now().format("%Y%m%d-%H%M%S-%f")));
+                tokio::fs::write(&path, email.formatted())
+                    .await
+                    .map_err(Error::MailFileError)?;
+            }
+            Transport::Log => {
+                log::info!(
+                    "Mail to {:?}:\n{}",
+                    email.envelope().to(),
+                    String::from_utf8_lossy(&email.formatted())
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+fn logo_content_type(path: &std::path::Path) -> ContentType {
+    let content_type = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    };
+    ContentType::parse(content_type).expect("Known content types are valid")
+}
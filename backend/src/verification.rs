@@ -0,0 +1,179 @@
+//! Double opt-in: registrations only count once the archer confirmed the email address
+//! with the link from the verification mail. Unconfirmed registrations expire.
+
+use crate::{
+    archer, auth,
+    config::EventConfig,
+    error::*,
+    models::{Status, TIMESTAMP_FORMAT},
+    outbox, repository, schema, CONFIG,
+};
+use axum::{extract::Path, Json};
+use chrono::{Duration, Local, NaiveDateTime};
+use common::archer::{Archer, Registration};
+use diesel::prelude::*;
+use std::collections::BTreeMap;
+
+/// How often expired registrations are deleted
+const CLEANUP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+pub fn new_token() -> String {
+    auth::random_token()
+}
+
+/// Time until which a registration made now has to be confirmed
+pub fn expiry_time() -> NaiveDateTime {
+    Local::now().naive_local() + Duration::hours(CONFIG.read().verification_hours.into())
+}
+
+fn verify_url(token: &str) -> String {
+    format!(
+        "{}/?verify={}",
+        CONFIG.read().public_url.trim_end_matches('/'),
+        token
+    )
+}
+
+pub async fn verify(Path(token): Path<String>) -> Result<Json<Registration>> {
+    let registration = tokio::task::spawn_blocking(move || -> Result<_> {
+        let bib = archer::confirm_archer(&token)?;
+        repository::load(&mut crate::db::establish_connection(), bib).map(Registration::from)
+    })
+    .await
+    .unwrap()?;
+    outbox::wake();
+    Ok(Json(registration))
+}
+
+pub fn queue_verification_mail(
+    conn: &mut SqliteConnection,
+    event: &EventConfig,
+    archer: &Archer,
+    token: &str,
+    expires: NaiveDateTime,
+) -> Result<()> {
+    let email_data =
+        verification_mail_data(event, &archer.first_name, &archer.last_name, token, expires);
+    let subject = {
+        let config = CONFIG.read();
+        config
+            .mail_message
+            .verification_subject
+            .clone()
+            .unwrap_or_else(|| config.mail_message.subject.clone())
+    };
+    outbox::enqueue(
+        conn,
+        format!("{} {}", archer.first_name, archer.last_name),
+        archer.mail.as_str(),
+        &subject,
+        "verification_mail",
+        &email_data,
+    )
+}
+
+pub fn verification_mail_data(
+    event: &EventConfig,
+    first_name: &str,
+    last_name: &str,
+    token: &str,
+    expires: NaiveDateTime,
+) -> BTreeMap<&'static str, String> {
+    BTreeMap::from([
+        ("event_name", event.name.clone()),
+        ("event_date", event.date.format("%d.%m.%Y").to_string()),
+        ("event_venue", event.venue.clone()),
+        ("first_name", first_name.to_string()),
+        ("last_name", last_name.to_string()),
+        ("verify_url", verify_url(token)),
+        (
+            "expires",
+            expires.format("%d.%m.%Y um %H:%M Uhr").to_string(),
+        ),
+    ])
+}
+
+/// Deletes unconfirmed registrations after their expiry time. Runs forever.
+pub async fn remove_expired_registrations() {
+    let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+    loop {
+        interval.tick().await;
+        let deleted = tokio::task::spawn_blocking(|| {
+            delete_expired(
+                &mut crate::db::establish_connection(),
+                Local::now().naive_local(),
+            )
+        });
+        match deleted.await.unwrap() {
+            Ok(0) => {}
+            Ok(deleted) => log::info!("Deleted {} unconfirmed registrations", deleted),
+            Err(_) => log::error!("Couldn't delete unconfirmed registrations"),
+        }
+    }
+}
+
+/// Deletes the unconfirmed registrations that expired before `now`
+fn delete_expired(conn: &mut SqliteConnection, now: NaiveDateTime) -> Result<usize> {
+    use schema::{archer_additions, archers};
+
+    let now = now.format(TIMESTAMP_FORMAT).to_string();
+    conn.immediate_transaction(|conn| -> Result<usize> {
+        let expired: Vec<i32> = archer_additions::table
+            .filter(archer_additions::status.eq(Status::Pending.as_str()))
+            .filter(archer_additions::verification_expires.lt(&now))
+            .select(archer_additions::bib)
+            .load(conn)?;
+        diesel::delete(archer_additions::table.filter(archer_additions::bib.eq_any(&expired)))
+            .execute(conn)?;
+        Ok(diesel::delete(archers::table.filter(archers::bib.eq_any(&expired))).execute(conn)?)
+    })
+}
+
+#[test]
+fn test_delete_expired() {
+    use crate::{models::OutboxMail, schema::outbox};
+
+    let mut conn = crate::db::test_connection();
+    let event = crate::config::test_event("");
+    let mut save = |mail| {
+        archer::save_archer(&mut conn, repository::test_archer(mail, 1), &event)
+            .ok()
+            .unwrap()
+    };
+    let confirmed = save("robin@example.com");
+    let pending = save("marian@example.com");
+    // The verification mails are queued with the registrations
+    let mails: Vec<OutboxMail> = outbox::table.load(&mut conn).unwrap();
+    assert_eq!(mails.len(), 2);
+    assert!(mails
+        .iter()
+        .all(|mail| mail.template == "verification_mail"));
+
+    let token: String = schema::archer_additions::table
+        .find(confirmed)
+        .select(schema::archer_additions::verification_token.assume_not_null())
+        .first(&mut conn)
+        .unwrap();
+    let (bib, expires) = repository::find_verification(&mut conn, &token)
+        .ok()
+        .unwrap()
+        .unwrap();
+    assert_eq!(bib, confirmed);
+    repository::set_status(&mut conn, confirmed, Status::Registered)
+        .ok()
+        .unwrap();
+
+    // Nothing expired yet
+    let now = Local::now().naive_local();
+    assert_eq!(delete_expired(&mut conn, now).ok(), Some(0));
+    let later = expires.unwrap() + Duration::minutes(1);
+    assert_eq!(delete_expired(&mut conn, later).ok(), Some(1));
+    assert!(matches!(
+        repository::load(&mut conn, pending),
+        Err(Error::NotFound)
+    ));
+    assert_eq!(
+        repository::load(&mut conn, confirmed).ok().unwrap().status,
+        Status::Registered
+    );
+}
@@ -0,0 +1,42 @@
+<!DOCTYPE html>
+<html lang="de">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{{event_name}}</title>
+</head>
+<body style="margin: 0; padding: 0; background-color: #f4f4f4; font-family: Arial, Helvetica, sans-serif; color: #222222;">
+<div style="max-width: 600px; margin: 0 auto; padding: 16px; background-color: #ffffff;">
+{{#if logo}}
+<p style="text-align: center;"><img src="cid:logo" alt="PSV München" style="max-width: 160px; height: auto;"></p>
+{{/if}}
+<p>Hallo {{first_name}},</p>
+<p>vielen Dank für deine Anmeldung zur <strong>{{event_name}}</strong> am {{event_date}} in {{event_venue}}.</p>
+{{#if waiting_list}}
+<p style="padding: 12px; background-color: #fff4d6;">Leider sind bereits alle Plätze vergeben. Du stehst deshalb auf der Warteliste.
+Sobald ein Platz frei wird, rückst du automatisch nach und bekommst eine weitere Mail.</p>
+{{/if}}
+<p>So hast du dich angemeldet:</p>
+<table style="width: 100%; border-collapse: collapse;">
+<tr><td style="padding: 4px 8px 4px 0; color: #666666;">Name</td><td style="padding: 4px 0;">{{first_name}} {{last_name}}</td></tr>
+{{#if date_of_birth}}
+<tr><td style="padding: 4px 8px 4px 0; color: #666666;">Geburtsdatum</td><td style="padding: 4px 0;">{{date_of_birth}}</td></tr>
+{{/if}}
+{{#if gender}}
+<tr><td style="padding: 4px 8px 4px 0; color: #666666;">Geschlecht</td><td style="padding: 4px 0;">{{gender}}</td></tr>
+{{/if}}
+<tr><td style="padding: 4px 8px 4px 0; color: #666666;">Klasse</td><td style="padding: 4px 0;">{{class}}</td></tr>
+<tr><td style="padding: 4px 8px 4px 0; color: #666666;">Scheibe</td><td style="padding: 4px 0;">{{target_face}}</td></tr>
+{{#if session}}
+<tr><td style="padding: 4px 8px 4px 0; color: #666666;">Durchgang</td><td style="padding: 4px 0;">{{session}}</td></tr>
+{{/if}}
+<tr><td style="padding: 4px 8px 4px 0; color: #666666;">Kommentar</td><td style="padding: 4px 0;">{{comment}}</td></tr>
+</table>
+<p style="margin: 24px 0; text-align: center;">
+<a href="{{manage_url}}" style="display: inline-block; padding: 12px 20px; background-color: #1f5fa8; color: #ffffff; text-decoration: none; border-radius: 4px;">Anmeldung ändern oder zurückziehen</a>
+</p>
+<p>Viele Grüße und Alle ins Gold<br>
+Sportleitung der Bogenabteilung des PSV München</p>
+</div>
+</body>
+</html>
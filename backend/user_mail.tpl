@@ -1,13 +1,21 @@
 Hallo {{first_name}},
 
-vielen Dank für deine Anmeldung zur Vereinsmeisterschaft WA am 21.05.2023.
-
+vielen Dank für deine Anmeldung zur {{event_name}} am {{event_date}} in {{event_venue}}.
+{{#if waiting_list}}
+Leider sind bereits alle Plätze vergeben. Du stehst deshalb auf der Warteliste.
+Sobald ein Platz frei wird, rückst du automatisch nach und bekommst eine weitere Mail.
+{{/if}}
 So hast du dich angemeldet:
 Name: {{first_name}} {{last_name}}
-Geburtsdatum: {{date_of_birth}}
-Klasse: {{class}}
+{{#if date_of_birth}}Geburtsdatum: {{date_of_birth}}
+{{/if}}{{#if gender}}Geschlecht: {{gender}}
+{{/if}}Klasse: {{class}}
 Scheibe: {{target_face}}
-Kommentar: {{comment}}
+{{#if session}}Durchgang: {{session}}
+{{/if}}Kommentar: {{comment}}
+
+Unter folgendem Link kannst du deine Anmeldung ändern oder zurückziehen:
+{{manage_url}}
 
 Viele Grüße und Alle ins Gold
 Sportleitung der Bogenabteilung des PSV München
\ No newline at end of file
@@ -0,0 +1,12 @@
+Hallo {{first_name}},
+
+du hast dich zur {{event_name}} am {{event_date}} in {{event_venue}} angemeldet.
+Bitte bestätige deine Email Adresse mit einem Klick auf folgenden Link:
+{{verify_url}}
+
+Erst dann ist deine Anmeldung gültig. Ohne Bestätigung bis zum {{expires}} wird sie wieder gelöscht.
+
+Falls du dich nicht angemeldet hast, kannst du diese Mail einfach ignorieren.
+
+Viele Grüße und Alle ins Gold
+Sportleitung der Bogenabteilung des PSV München
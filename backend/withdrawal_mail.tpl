@@ -0,0 +1,7 @@
+Hallo {{first_name}},
+
+du hast deine Anmeldung zur {{event_name}} am {{event_date}} in {{event_venue}} zurückgezogen.
+Schade, dass du nicht dabei bist!
+
+Viele Grüße und Alle ins Gold
+Sportleitung der Bogenabteilung des PSV München
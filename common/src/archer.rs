@@ -2,55 +2,448 @@ use chrono::NaiveDate;
 use email_address::EmailAddress;
 use serde::{Deserialize, Serialize};
 
-use crate::{class::Class, target_face::TargetFace};
+use crate::{class::Class, event::EventInfo, gender::Gender, target_face::TargetFace};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Archer {
     pub first_name: String,
     pub last_name: String,
-    pub mail: EmailAddress,
+    /// Plain text, so invalid addresses are reported by [`Archer::validate`]
+    pub mail: String,
     pub comment: String,
     date_of_birth: NaiveDate,
+    gender: Gender,
     class: Class,
     target_face: TargetFace,
+    #[serde(default = "default_session")]
+    session: i32,
+}
+
+fn default_session() -> i32 {
+    1
 }
 
 impl Archer {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         first_name: String,
         last_name: String,
-        mail: EmailAddress,
+        mail: &str,
         dob: NaiveDate,
+        gender: Gender,
         cls: Class,
         target_face: TargetFace,
+        session: i32,
         comment: String,
-    ) -> Result<Self, ()> {
-        if !Class::all_classes()
-            .filter(|c| c.in_range(dob))
-            .any(|c| c == cls)
-        {
-            return Err(());
-        }
-        if !TargetFace::for_cls(cls).contains(&target_face) {
-            return Err(());
-        }
-        Ok(Self {
+        event: &EventInfo,
+    ) -> Result<Self, Vec<RegistrationError>> {
+        let archer = Self::new_unchecked(
             first_name,
             last_name,
             mail,
+            dob,
+            gender,
+            cls,
+            target_face,
+            session,
+            comment,
+        );
+        archer.validate(event)?;
+        Ok(archer)
+    }
+    /// Archer with data that was checked before, e.g. when it was stored.
+    /// The rules may have changed since, [`Archer::validate`] checks it against the current ones.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_unchecked(
+        first_name: String,
+        last_name: String,
+        mail: &str,
+        dob: NaiveDate,
+        gender: Gender,
+        cls: Class,
+        target_face: TargetFace,
+        session: i32,
+        comment: String,
+    ) -> Self {
+        Self {
+            first_name,
+            last_name,
+            mail: mail.to_string(),
             date_of_birth: dob,
+            gender,
             class: cls,
             target_face,
+            session,
             comment,
-        })
+        }
+    }
+    /// Checks the rules enforced by [`Archer::new`].
+    /// Deserialized archers bypass `new` and have to be checked with this.
+    pub fn validate(&self, event: &EventInfo) -> Result<(), Vec<RegistrationError>> {
+        let mut errors = Vec::new();
+        if self.first_name.trim().is_empty() {
+            errors.push(RegistrationError::EmptyName {
+                field: NameField::FirstName,
+            });
+        }
+        if self.last_name.trim().is_empty() {
+            errors.push(RegistrationError::EmptyName {
+                field: NameField::LastName,
+            });
+        }
+        if !EmailAddress::is_valid(&self.mail) {
+            errors.push(RegistrationError::InvalidEmail {
+                mail: self.mail.clone(),
+            });
+        }
+        if !event.classes.contains(&self.class) {
+            errors.push(RegistrationError::ClassNotOffered {
+                class: self.class.clone(),
+            });
+        }
+        // Unknown classes are reported as not offered only, the other checks need the definition
+        if self.class.definition().is_some() {
+            if !self.class.in_range(self.date_of_birth, event.season_start) {
+                errors.push(RegistrationError::ClassNotInAgeRange {
+                    class: self.class.clone(),
+                    date_of_birth: self.date_of_birth,
+                });
+            }
+            if !self.class.allows(self.gender) {
+                errors.push(RegistrationError::ClassNotForGender {
+                    class: self.class.clone(),
+                    gender: self.gender,
+                });
+            }
+            if !event
+                .target_faces_for(&self.class)
+                .contains(&self.target_face)
+            {
+                errors.push(RegistrationError::TargetFaceNotAllowed {
+                    class: self.class.clone(),
+                    target_face: self.target_face,
+                });
+            }
+        }
+        let session_offered = if event.sessions.is_empty() {
+            self.session == default_session()
+        } else {
+            event.session(self.session).is_some()
+        };
+        if !session_offered {
+            errors.push(RegistrationError::UnknownSession {
+                session: self.session,
+            });
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
     pub fn date_of_birth(&self) -> NaiveDate {
         self.date_of_birth
     }
-    pub fn class(&self) -> Class {
-        self.class
+    pub fn gender(&self) -> Gender {
+        self.gender
+    }
+    pub fn class(&self) -> &Class {
+        &self.class
     }
     pub fn target_face(&self) -> TargetFace {
         self.target_face
     }
+    pub fn session(&self) -> i32 {
+        self.session
+    }
+}
+
+/// A stored registration as shown to the archer on the self-service page
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Registration {
+    pub event: i32,
+    pub waiting_list: bool,
+    pub first_name: String,
+    pub last_name: String,
+    pub mail: String,
+    pub comment: String,
+    /// Unknown for registrations from before it was stored
+    pub date_of_birth: Option<NaiveDate>,
+    /// Unknown for registrations from before it was stored
+    pub gender: Option<Gender>,
+    pub class: Option<Class>,
+    pub target_face: Option<TargetFace>,
+    pub session: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameField {
+    FirstName,
+    LastName,
+}
+
+/// Reasons why a registration is rejected
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "kind")]
+pub enum RegistrationError {
+    EmptyName {
+        field: NameField,
+    },
+    InvalidEmail {
+        mail: String,
+    },
+    /// The address of a stored registration is confirmed and can't be changed
+    MailChanged {
+        mail: String,
+    },
+    ClassNotOffered {
+        class: Class,
+    },
+    ClassNotInAgeRange {
+        class: Class,
+        date_of_birth: NaiveDate,
+    },
+    ClassNotForGender {
+        class: Class,
+        gender: Gender,
+    },
+    TargetFaceNotAllowed {
+        class: Class,
+        target_face: TargetFace,
+    },
+    UnknownSession {
+        session: i32,
+    },
+}
+
+impl RegistrationError {
+    /// Name of the [`Archer`] field the error refers to
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::EmptyName {
+                field: NameField::FirstName,
+            } => "first_name",
+            Self::EmptyName {
+                field: NameField::LastName,
+            } => "last_name",
+            Self::InvalidEmail { .. } => "mail",
+            Self::MailChanged { .. } => "mail",
+            Self::ClassNotOffered { .. } => "class",
+            Self::ClassNotInAgeRange { .. } => "class",
+            Self::ClassNotForGender { .. } => "class",
+            Self::TargetFaceNotAllowed { .. } => "target_face",
+            Self::UnknownSession { .. } => "session",
+        }
+    }
+    pub fn message_de(&self) -> String {
+        match self {
+            Self::EmptyName {
+                field: NameField::FirstName,
+            } => "Der Vorname darf nicht leer sein".to_string(),
+            Self::EmptyName {
+                field: NameField::LastName,
+            } => "Der Nachname darf nicht leer sein".to_string(),
+            Self::InvalidEmail { mail } => format!("\"{mail}\" ist keine gültige Email Adresse"),
+            Self::MailChanged { mail } => format!(
+                "Die Email Adresse {mail} kann nicht geändert werden. Melde dich ab und neu an, um eine andere Adresse zu verwenden."
+            ),
+            Self::ClassNotOffered { class } => format!(
+                "Die Klasse {} wird bei dieser Veranstaltung nicht angeboten",
+                class.name()
+            ),
+            Self::ClassNotInAgeRange {
+                class,
+                date_of_birth,
+            } => format!(
+                "Die Klasse {} passt nicht zum Geburtsdatum {}",
+                class.name(),
+                date_of_birth.format("%d.%m.%Y")
+            ),
+            Self::ClassNotForGender { class, gender } => format!(
+                "Die Klasse {} passt nicht zum Geschlecht {}",
+                class.name(),
+                gender.name()
+            ),
+            Self::TargetFaceNotAllowed { class, target_face } => format!(
+                "Die Auflage {} ist in der Klasse {} nicht erlaubt",
+                target_face,
+                class.name()
+            ),
+            Self::UnknownSession { session } => {
+                format!("Den Durchgang {session} gibt es bei dieser Veranstaltung nicht")
+            }
+        }
+    }
+    pub fn message_en(&self) -> String {
+        match self {
+            Self::EmptyName {
+                field: NameField::FirstName,
+            } => "The first name must not be empty".to_string(),
+            Self::EmptyName {
+                field: NameField::LastName,
+            } => "The last name must not be empty".to_string(),
+            Self::InvalidEmail { mail } => format!("\"{mail}\" is not a valid email address"),
+            Self::MailChanged { mail } => format!(
+                "The email address {mail} can't be changed. Withdraw and register again to use another address."
+            ),
+            Self::ClassNotOffered { class } => {
+                format!("The class {} is not offered at this event", class.name())
+            }
+            Self::ClassNotInAgeRange {
+                class,
+                date_of_birth,
+            } => format!(
+                "The class {} doesn't match the date of birth {}",
+                class.name(),
+                date_of_birth.format("%Y-%m-%d")
+            ),
+            Self::ClassNotForGender { class, gender } => format!(
+                "The class {} doesn't match the gender {}",
+                class.name(),
+                match gender {
+                    Gender::Male => "male",
+                    Gender::Female => "female",
+                }
+            ),
+            Self::TargetFaceNotAllowed { class, target_face } => format!(
+                "The target face {} is not allowed in the class {}",
+                target_face,
+                class.name()
+            ),
+            Self::UnknownSession { session } => {
+                format!("There is no session {session} at this event")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for RegistrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message_de())
+    }
+}
+
+#[test]
+fn test_validate() {
+    let event = EventInfo {
+        season_start: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+        classes: vec![Class::new("R10"), Class::new("R22")],
+        target_faces: vec![TargetFace::Spot, TargetFace::Cm40, TargetFace::Cm80],
+        ..Default::default()
+    };
+    let dob = NaiveDate::from_ymd_opt(1990, 6, 1).unwrap();
+    assert!(Archer::new(
+        "Robin".into(),
+        "Hood".into(),
+        "robin@example.com",
+        dob,
+        Gender::Male,
+        Class::new("R10"),
+        TargetFace::Spot,
+        1,
+        String::new(),
+        &event
+    )
+    .is_ok());
+
+    let errors = Archer::new(
+        " ".into(),
+        "Hood".into(),
+        "robin",
+        dob,
+        Gender::Female,
+        Class::new("R22"),
+        TargetFace::Spot,
+        1,
+        String::new(),
+        &event,
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        [
+            RegistrationError::EmptyName {
+                field: NameField::FirstName
+            },
+            RegistrationError::InvalidEmail {
+                mail: "robin".into()
+            },
+            RegistrationError::ClassNotInAgeRange {
+                class: Class::new("R22"),
+                date_of_birth: dob
+            },
+            RegistrationError::ClassNotForGender {
+                class: Class::new("R22"),
+                gender: Gender::Female
+            },
+            RegistrationError::TargetFaceNotAllowed {
+                class: Class::new("R22"),
+                target_face: TargetFace::Spot
+            },
+        ]
+    );
+
+    let errors = Archer::new(
+        "Robin".into(),
+        "Hood".into(),
+        "robin@example.com",
+        dob,
+        Gender::Female,
+        Class::new("R11"),
+        TargetFace::Spot,
+        2,
+        String::new(),
+        &event,
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        [
+            RegistrationError::ClassNotOffered {
+                class: Class::new("R11")
+            },
+            RegistrationError::UnknownSession { session: 2 }
+        ]
+    );
+
+    let errors = Archer::new(
+        "Robin".into(),
+        "Hood".into(),
+        "robin@example.com",
+        dob,
+        Gender::Male,
+        Class::new("R99"),
+        TargetFace::Spot,
+        1,
+        String::new(),
+        &event,
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        [RegistrationError::ClassNotOffered {
+            class: Class::new("R99")
+        }]
+    );
+
+    // Invalid addresses reach the validation instead of failing the deserialization
+    let archer = Archer::new_unchecked(
+        "Robin".into(),
+        "Hood".into(),
+        "robin",
+        dob,
+        Gender::Male,
+        Class::new("R10"),
+        TargetFace::Spot,
+        1,
+        String::new(),
+    );
+    let archer: Archer = toml::Value::try_from(archer).unwrap().try_into().unwrap();
+    assert_eq!(
+        archer.validate(&event).unwrap_err(),
+        [RegistrationError::InvalidEmail {
+            mail: "robin".into()
+        }]
+    );
 }
@@ -1,195 +1,259 @@
-use crate::bow_type::BowType;
+use crate::{bow_type::BowType, gender::Gender, target_face::TargetFace};
 use chrono::{Months, NaiveDate};
-use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use strum::IntoEnumIterator;
+use std::{
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
 
-lazy_static! {
-    pub static ref SEASON_START: NaiveDate = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+/// Id of a class, e.g. `R10`. Which classes exist and their names, age ranges etc.
+/// come from the [`ClassRules`], so new classes need no new release.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Class(String);
+
+/// Only accepts classes defined in the current rules
+impl FromStr for Class {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let cls = Class::new(code);
+        match cls.definition() {
+            Some(_) => Ok(cls),
+            None => Err(format!("Unknown class {code}")),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, strum::EnumIter)]
-pub enum Class {
-    R10,
-    R11,
-    R20,
-    R21,
-    R22,
-    R23,
-    R30,
-    R31,
-    R40,
-    R41,
-    R12,
-    R13,
-    R14,
-    R15,
-    #[serde(rename = "B210")]
-    B10,
-    #[serde(rename = "B211")]
-    B11,
-    #[serde(rename = "B220")]
-    B20,
-    #[serde(rename = "B230")]
-    B30,
-    #[serde(rename = "B120")]
-    B12,
-    #[serde(rename = "C110")]
-    C10,
-    #[serde(rename = "C111")]
-    C11,
-    #[serde(rename = "C120")]
-    C20,
-    #[serde(rename = "C130")]
-    C30,
-    #[serde(rename = "C140")]
-    C40,
-    #[serde(rename = "C112")]
-    C12,
-    #[serde(rename = "C113")]
-    C13,
-    #[serde(rename = "C114")]
-    C14,
-    OO,
+/// Definitions of the classes, read from a rules file so rule changes need no new release.
+/// `classes.toml` holds the built-in rules.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ClassRules {
+    /// Comment of the classes without their own
+    #[serde(default)]
+    pub default_comment: String,
+    pub classes: Vec<ClassDefinition>,
 }
 
-impl Class {
-    pub fn name(&self) -> &'static str {
-        match self {
-            Class::R10 => "Recurve Herren",
-            Class::R11 => "Recurve Damen",
-            Class::R20 => "Recurve Schüler A m",
-            Class::R21 => "Recurve Schüler A w",
-            Class::R22 => "Recurve Schüler B m",
-            Class::R23 => "Recurve Schüler B w",
-            Class::R30 => "Recurve Jugend m",
-            Class::R31 => "Recurve Jugend w",
-            Class::R40 => "Recurve Junioren m",
-            Class::R41 => "Recurve Junioren w",
-            Class::R12 => "Recurve Master m",
-            Class::R13 => "Recurve Master w",
-            Class::R14 => "Recurve Senioren m",
-            Class::R15 => "Recurve Senioren w",
-            Class::B10 => "Blank Herren",
-            Class::B11 => "Blank Damen",
-            Class::B20 => "Blank Schüler m/w",
-            Class::B30 => "Blank Jugend m/m",
-            Class::B12 => "Blank Master m",
-            Class::C10 => "Compound Herren",
-            Class::C11 => "Compound Damen",
-            Class::C20 => "Compound Schüler m/w",
-            Class::C30 => "Compound Jugend m/m",
-            Class::C40 => "Compound Junioren m/w",
-            Class::C12 => "Compound Master m",
-            Class::C13 => "Compound Master w",
-            Class::C14 => "Compound Senioren m",
-            Class::OO => "Offene Klasse",
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ClassDefinition {
+    pub id: Class,
+    /// Class in Ianseo
+    pub ianseo_code: String,
+    pub name: String,
+    /// Bow type of the class. Open to every bow if omitted
+    pub division: Option<BowType>,
+    /// Open to everybody if omitted
+    pub gender: Option<Gender>,
+    /// Age reached in the year of the season start
+    pub min_age: u32,
+    pub max_age: u32,
+    pub target_faces: Vec<TargetFace>,
+    /// Replaces the `default_comment` of the rules
+    pub comment: Option<String>,
+}
+
+const BUILTIN_RULES: &str = include_str!("../classes.toml");
+
+static CURRENT_RULES: RwLock<Option<Arc<ClassRules>>> = RwLock::new(None);
+
+impl ClassRules {
+    pub fn from_toml(content: &str) -> Result<Self, String> {
+        let rules: Self = toml::from_str(content).map_err(|e| e.to_string())?;
+        rules.validate()?;
+        Ok(rules)
+    }
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, definition) in self.classes.iter().enumerate() {
+            if self.classes[..i]
+                .iter()
+                .any(|other| other.id == definition.id)
+            {
+                return Err(format!(
+                    "The class {} is defined twice",
+                    definition.id.code()
+                ));
+            }
+            if definition.id.code().is_empty() {
+                return Err(format!("The class {} has no id", definition.name));
+            }
+            if definition.min_age > definition.max_age {
+                return Err(format!(
+                    "The class {} has a minimum age above its maximum age",
+                    definition.name
+                ));
+            }
         }
+        Ok(())
     }
-    pub fn comment(&self) -> &'static str {
-        match self{
-            Class::OO => "Eine Klasse für alle. Die Auflage ist größer als bei den offizielen Klassen. Dadurch ist eine Qualifikation zur Bezirksmeisterschaft ausgeschlossen.",
-            _ => "Reguläre Klasse nach Sportornung. Eine Weitermeldung zur Bezirksmeisterschaft ist möglich"
+    /// Rules used by the methods of [`Class`]. The built-in rules until [`ClassRules::set_current`] is called.
+    pub fn current() -> Arc<ClassRules> {
+        if let Some(rules) = &*CURRENT_RULES.read().unwrap() {
+            return rules.clone();
         }
+        CURRENT_RULES
+            .write()
+            .unwrap()
+            .get_or_insert_with(|| Arc::new(ClassRules::default()))
+            .clone()
     }
-    pub fn all_classes() -> impl Iterator<Item = Self> {
-        Self::iter()
-    }
-    pub fn recurve_classes() -> &'static [Self] {
-        &[
-            Self::R10,
-            Self::R11,
-            Self::R20,
-            Self::R21,
-            Self::R22,
-            Self::R23,
-            Self::R30,
-            Self::R31,
-            Self::R40,
-            Self::R41,
-            Self::R12,
-            Self::R13,
-            Self::R14,
-            Self::R15,
-            Self::OO,
-        ]
-    }
-    pub fn barebow_classes() -> &'static [Self] {
-        &[
-            Self::B10,
-            Self::B11,
-            Self::B20,
-            Self::B30,
-            Self::B12,
-            Self::OO,
-        ]
-    }
-    pub fn compound_classes() -> &'static [Self] {
-        &[
-            Self::C10,
-            Self::C11,
-            Self::C20,
-            Self::C30,
-            Self::C40,
-            Self::C12,
-            Self::C13,
-            Self::C14,
-            Self::OO,
-        ]
-    }
-    pub fn in_range(&self, dob: NaiveDate) -> bool {
-        let year_range = match self {
-            Class::R10 => (21, 49),
-            Class::R11 => (21, 49),
-            Class::R20 => (13, 14),
-            Class::R21 => (13, 14),
-            Class::R22 => (11, 12),
-            Class::R23 => (11, 12),
-            Class::R30 => (15, 17),
-            Class::R31 => (15, 17),
-            Class::R40 => (18, 20),
-            Class::R41 => (18, 20),
-            Class::R12 => (50, 65),
-            Class::R13 => (50, 65),
-            Class::R14 => (66, 120),
-            Class::R15 => (66, 120),
-            Class::C10 => (21, 49),
-            Class::C11 => (21, 49),
-            Class::C20 => (1, 14),
-            Class::C30 => (15, 17),
-            Class::C40 => (18, 20),
-            Class::C12 => (50, 65),
-            Class::C13 => (50, 120),
-            Class::C14 => (66, 120),
-            Class::B10 => (21, 49),
-            Class::B11 => (21, 120),
-            Class::B20 => (1, 14),
-            Class::B30 => (15, 20),
-            Class::B12 => (50, 120),
-            Class::OO => (15, 120),
-        };
-
-        let date_range = (*SEASON_START - Months::new(year_range.1 * 12))
-            ..(*SEASON_START - Months::new((year_range.0 - 1) * 12));
+    pub fn set_current(rules: ClassRules) {
+        *CURRENT_RULES.write().unwrap() = Some(Arc::new(rules));
+    }
+    pub fn get(&self, cls: &Class) -> Option<&ClassDefinition> {
+        self.classes.iter().find(|definition| &definition.id == cls)
+    }
+}
+
+impl Default for ClassRules {
+    fn default() -> Self {
+        Self::from_toml(BUILTIN_RULES).expect("Built-in class rules are valid")
+    }
+}
+
+impl ClassDefinition {
+    pub fn in_range(&self, dob: NaiveDate, season_start: NaiveDate) -> bool {
+        let date_range = (season_start - Months::new(self.max_age * 12))
+            ..(season_start - Months::new(self.min_age.saturating_sub(1) * 12));
         date_range.contains(&dob)
     }
-    pub fn classes_for(dob: NaiveDate, bow_type: BowType) -> Vec<Class> {
-        match bow_type {
-            BowType::Recurve => Self::recurve_classes(),
-            BowType::Compound => Self::compound_classes(),
-            BowType::Barebow => Self::barebow_classes(),
+}
+
+impl Class {
+    /// Class with the given id, whether the rules define it or not. Use [`str::parse`]
+    /// to accept only defined classes.
+    pub fn new(code: &str) -> Self {
+        Class(code.to_string())
+    }
+    /// Stable code of the class, stored in the database and used in the config and the API
+    pub fn code(&self) -> &str {
+        &self.0
+    }
+    /// Definition in the current rules. Classes without one can't be chosen.
+    pub fn definition(&self) -> Option<ClassDefinition> {
+        ClassRules::current().get(self).cloned()
+    }
+    pub fn name(&self) -> String {
+        match self.definition() {
+            Some(definition) => definition.name,
+            None => self.code().to_string(),
         }
-        .iter()
-        .filter(|c| c.in_range(dob))
-        .copied()
-        .collect()
+    }
+    pub fn comment(&self) -> String {
+        let rules = ClassRules::current();
+        rules
+            .get(self)
+            .and_then(|definition| definition.comment.clone())
+            .unwrap_or_else(|| rules.default_comment.clone())
+    }
+    /// Classes defined in the current rules
+    pub fn all_classes() -> impl Iterator<Item = Self> {
+        ClassRules::current()
+            .classes
+            .iter()
+            .map(|definition| definition.id.clone())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+    /// Bow type of the class. Classes open to every bow count as recurve
+    pub fn bow_type(&self) -> BowType {
+        self.definition()
+            .and_then(|definition| definition.division)
+            .unwrap_or_default()
+    }
+    pub fn in_range(&self, dob: NaiveDate, season_start: NaiveDate) -> bool {
+        self.definition()
+            .is_some_and(|definition| definition.in_range(dob, season_start))
+    }
+    /// Whether archers of the gender can start in the class
+    pub fn allows(&self, gender: Gender) -> bool {
+        self.definition()
+            .is_some_and(|definition| definition.gender.is_none_or(|g| g == gender))
+    }
+    /// Target faces allowed in the class
+    pub fn target_faces(&self) -> Vec<TargetFace> {
+        self.definition()
+            .map(|definition| definition.target_faces)
+            .unwrap_or_default()
+    }
+    pub fn classes_for(
+        dob: NaiveDate,
+        bow_type: BowType,
+        gender: Gender,
+        season_start: NaiveDate,
+    ) -> Vec<Class> {
+        ClassRules::current()
+            .classes
+            .iter()
+            .filter(|definition| {
+                definition
+                    .division
+                    .is_none_or(|division| division == bow_type)
+            })
+            .filter(|definition| definition.gender.is_none_or(|g| g == gender))
+            .filter(|definition| definition.in_range(dob, season_start))
+            .map(|definition| definition.id.clone())
+            .collect()
     }
 }
 
 #[test]
 fn test_in_range() {
-    assert!(!Class::R10.in_range(NaiveDate::from_ymd_opt(1973, 12, 31).unwrap()));
-    assert!(Class::R10.in_range(NaiveDate::from_ymd_opt(1974, 1, 1).unwrap()));
-    assert!(Class::R10.in_range(NaiveDate::from_ymd_opt(2002, 12, 31).unwrap()));
-    assert!(!Class::R10.in_range(NaiveDate::from_ymd_opt(2003, 1, 1).unwrap()));
+    let season_start = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+    let r10 = Class::new("R10");
+    assert!(!r10.in_range(NaiveDate::from_ymd_opt(1973, 12, 31).unwrap(), season_start));
+    assert!(r10.in_range(NaiveDate::from_ymd_opt(1974, 1, 1).unwrap(), season_start));
+    assert!(r10.in_range(NaiveDate::from_ymd_opt(2002, 12, 31).unwrap(), season_start));
+    assert!(!r10.in_range(NaiveDate::from_ymd_opt(2003, 1, 1).unwrap(), season_start));
+}
+
+#[test]
+fn test_code() {
+    assert_eq!("B210".parse(), Ok(Class::new("B210")));
+    assert!("B10".parse::<Class>().is_err());
+    // The config and the API use the codes
+    assert_eq!(
+        toml::Value::try_from(Class::new("B210")).unwrap().as_str(),
+        Some("B210")
+    );
+}
+
+#[test]
+fn test_class_rules() {
+    let rules = ClassRules::default();
+    assert_eq!(rules.get(&Class::new("B210")).unwrap().ianseo_code, "B10");
+    let season_start = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+    let dob = NaiveDate::from_ymd_opt(1990, 6, 1).unwrap();
+    assert_eq!(
+        Class::classes_for(dob, BowType::Barebow, Gender::Female, season_start),
+        [Class::new("B211"), Class::new("OO")]
+    );
+
+    // Classes unknown to the built-in rules
+    let rules = ClassRules::from_toml(
+        r#"
+        [[classes]]
+        id = "R50"
+        ianseo_code = "R50"
+        name = "Recurve Ü65"
+        division = "Recurve"
+        min_age = 65
+        max_age = 120
+        target_faces = ["Cm122"]
+    "#,
+    )
+    .unwrap();
+    assert_eq!(rules.get(&Class::new("R50")).unwrap().name, "Recurve Ü65");
+
+    let duplicate = r#"
+        [[classes]]
+        id = "OO"
+        ianseo_code = "OO"
+        name = "Offene Klasse"
+        min_age = 1
+        max_age = 120
+        target_faces = ["Cm60"]
+    "#;
+    assert!(ClassRules::from_toml(duplicate).is_ok());
+    assert!(ClassRules::from_toml(&format!("{duplicate}{duplicate}")).is_err());
 }
@@ -0,0 +1,122 @@
+use crate::{bow_type::BowType, class::Class, gender::Gender, target_face::TargetFace};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+/// General information about a tournament archers can register for
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EventInfo {
+    pub id: i32,
+    pub name: String,
+    pub date: NaiveDate,
+    pub venue: String,
+    /// Reference date for the age classes. Usually January 1st of the season's year.
+    pub season_start: NaiveDate,
+    /// Classes offered at this event
+    pub classes: Vec<Class>,
+    /// Target faces offered at this event
+    pub target_faces: Vec<TargetFace>,
+    /// Sessions archers can choose from. Everybody shoots in session 1 if empty
+    pub sessions: Vec<SessionInfo>,
+    /// Local time from which on registrations are accepted
+    pub registration_opens: Option<NaiveDateTime>,
+    /// Local time from which on registrations are rejected
+    pub registration_closes: Option<NaiveDateTime>,
+    /// Local time of the server when it sent the info. The registration window depends on
+    /// the server's clock, not the one of the visitor.
+    #[serde(default)]
+    pub server_time: Option<NaiveDateTime>,
+}
+
+/// Whether registrations are accepted at a point in time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistrationWindow {
+    NotYetOpen(NaiveDateTime),
+    Open,
+    Closed(NaiveDateTime),
+}
+
+/// A session ("Durchgang") of an event with its own start time and limits
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SessionInfo {
+    /// Session number as used by Ianseo
+    pub id: i32,
+    pub name: String,
+    /// Start time as `HH:MM:SS`
+    pub start: NaiveTime,
+    /// Maximum number of archers
+    pub max_archers: Option<u32>,
+    /// Maximum number of target butts
+    pub max_butts: Option<u32>,
+    #[serde(default = "default_archers_per_butt")]
+    pub archers_per_butt: u32,
+}
+
+fn default_archers_per_butt() -> u32 {
+    4
+}
+
+impl SessionInfo {
+    /// Number of archers fitting into the session, if it is limited
+    pub fn capacity(&self) -> Option<u32> {
+        let by_butts = self.max_butts.map(|butts| butts * self.archers_per_butt);
+        match (self.max_archers, by_butts) {
+            (Some(archers), Some(butts)) => Some(archers.min(butts)),
+            (archers, butts) => archers.or(butts),
+        }
+    }
+}
+
+impl EventInfo {
+    pub fn registration_window(&self, now: NaiveDateTime) -> RegistrationWindow {
+        match (self.registration_opens, self.registration_closes) {
+            (Some(opens), _) if now < opens => RegistrationWindow::NotYetOpen(opens),
+            (_, Some(closes)) if now >= closes => RegistrationWindow::Closed(closes),
+            _ => RegistrationWindow::Open,
+        }
+    }
+    pub fn session(&self, id: i32) -> Option<&SessionInfo> {
+        self.sessions.iter().find(|session| session.id == id)
+    }
+    /// Classes of this event matching the date of birth, bow type and gender
+    pub fn classes_for(&self, dob: NaiveDate, bow_type: BowType, gender: Gender) -> Vec<Class> {
+        Class::classes_for(dob, bow_type, gender, self.season_start)
+            .into_iter()
+            .filter(|cls| self.classes.contains(cls))
+            .collect()
+    }
+    /// Target faces of this event allowed in the class
+    pub fn target_faces_for(&self, cls: &Class) -> Vec<TargetFace> {
+        cls.target_faces()
+            .into_iter()
+            .filter(|tf| self.target_faces.contains(tf))
+            .collect()
+    }
+}
+
+#[test]
+fn test_registration_window() {
+    let at = |day| {
+        NaiveDate::from_ymd_opt(2023, 4, day)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    };
+    let event = EventInfo {
+        registration_opens: Some(at(1)),
+        registration_closes: Some(at(10)),
+        ..Default::default()
+    };
+    assert_eq!(
+        event.registration_window(at(1) - chrono::Duration::seconds(1)),
+        RegistrationWindow::NotYetOpen(at(1))
+    );
+    assert_eq!(event.registration_window(at(1)), RegistrationWindow::Open);
+    assert_eq!(
+        event.registration_window(at(10)),
+        RegistrationWindow::Closed(at(10))
+    );
+    assert_eq!(
+        EventInfo::default().registration_window(at(10)),
+        RegistrationWindow::Open
+    );
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Gender {
+    Male,
+    Female,
+}
+
+impl Gender {
+    /// Value of Ianseo's `gender` column
+    pub fn ianseo_code(&self) -> i32 {
+        match self {
+            Gender::Male => 0,
+            Gender::Female => 1,
+        }
+    }
+    pub fn from_ianseo_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(Gender::Male),
+            1 => Some(Gender::Female),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Gender::Male => "männlich",
+            Gender::Female => "weiblich",
+        }
+    }
+}
@@ -1,4 +1,6 @@
 pub mod archer;
 pub mod bow_type;
 pub mod class;
+pub mod event;
+pub mod gender;
 pub mod target_face;
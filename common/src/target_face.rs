@@ -1,7 +1,8 @@
-use crate::class::Class;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use strum::IntoEnumIterator;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, strum::EnumIter)]
 pub enum TargetFace {
     Spot,
     Cm40,
@@ -11,19 +12,28 @@ pub enum TargetFace {
 }
 
 impl TargetFace {
-    pub fn for_cls(cls: Class) -> &'static [TargetFace] {
-        use Class::*;
-        use TargetFace::*;
-        match cls {
-            C10 | C11 | C30 | C40 | C12 | C13 | C14 => &[Spot],
-            R10 | R11 | R40 | R41 | R12 | R13 => &[Spot, Cm40],
-            R30 | R31 | R14 | R15 | B10 | B11 | B12 | B30 => &[Cm40],
-            R20 | R21 | B20 | C20 | OO => &[Cm60],
-            R22 | R23 => &[Cm80],
+    /// Stable code of the target face, stored in the database
+    pub fn code(&self) -> &'static str {
+        match self {
+            TargetFace::Spot => "spot",
+            TargetFace::Cm40 => "40cm",
+            TargetFace::Cm60 => "60cm",
+            TargetFace::Cm80 => "80cm",
+            TargetFace::Cm122 => "122cm",
         }
     }
 }
 
+impl FromStr for TargetFace {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        Self::iter()
+            .find(|tf| tf.code() == code)
+            .ok_or_else(|| format!("Unknown target face {code}"))
+    }
+}
+
 impl std::fmt::Display for TargetFace {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -39,3 +49,10 @@ impl std::fmt::Display for TargetFace {
         )
     }
 }
+
+#[test]
+fn test_code() {
+    for tf in TargetFace::iter() {
+        assert_eq!(tf.code().parse(), Ok(tf));
+    }
+}
@@ -1,9 +1,16 @@
 use email_address::EmailAddress;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Display, str::FromStr};
+use std::fmt::Display;
 
 use chrono::prelude::*;
-use common::{bow_type::BowType, class::Class, target_face::TargetFace};
+use common::{
+    archer::{Registration, RegistrationError},
+    bow_type::BowType,
+    class::{Class, ClassRules},
+    event::{EventInfo, RegistrationWindow},
+    gender::Gender,
+    target_face::TargetFace,
+};
 use seed::{prelude::*, *};
 
 #[derive(Serialize, Deserialize)]
@@ -11,6 +18,9 @@ struct Model {
     first_name: String,
     last_name: String,
     date_of_birth: NaiveDate,
+    /// Unset until chosen, there is no sensible default
+    #[serde(default)]
+    gender: Option<Gender>,
     mail: InsertedMail,
     bow_type: BowType,
     cls: Option<Class>,
@@ -18,8 +28,26 @@ struct Model {
 
     possible_target_faces: Vec<TargetFace>,
     selected_target_face: TargetFace,
+    session: i32,
 
     submitting: bool,
+    /// Id of the event the archer registers for
+    event_id: Option<i32>,
+    /// Always fetched from the server, the events might have changed since the model was stored
+    #[serde(skip)]
+    events: Option<Vec<EventInfo>>,
+    /// How far the server's clock is ahead of the local one when the events were loaded
+    #[serde(skip)]
+    clock_offset: Option<chrono::Duration>,
+    /// Set if an existing registration was opened via the link in the confirmation mail
+    #[serde(skip)]
+    manage: Option<Manage>,
+}
+
+struct Manage {
+    token: String,
+    loaded: bool,
+    waiting_list: bool,
 }
 
 thread_local! {
@@ -28,42 +56,68 @@ thread_local! {
 
 impl Model {
     fn new() -> Self {
-        let date = NaiveDate::default();
-        let cls = Class::classes_for(date, BowType::Recurve)[0];
         Model {
             first_name: String::new(),
             last_name: String::new(),
-            date_of_birth: date,
+            date_of_birth: NaiveDate::default(),
+            gender: None,
             mail: InsertedMail::Invalid(String::new()),
             bow_type: BowType::Recurve,
-            cls: Some(cls),
+            cls: None,
             comment: String::new(),
-            possible_target_faces: TargetFace::for_cls(cls).to_owned(),
-            selected_target_face: TargetFace::for_cls(cls)[0],
+            possible_target_faces: Vec::new(),
+            selected_target_face: TargetFace::Cm40,
+            session: 1,
             submitting: false,
+            event_id: None,
+            events: None,
+            clock_offset: None,
+            manage: None,
         }
     }
-    fn check_and_update_cls(&mut self, orders: &mut impl Orders<Msg>) {
-        let available_classes: Vec<Class> = match self.bow_type {
-            BowType::Recurve => Class::recurve_classes(),
-            BowType::Compound => Class::compound_classes(),
-            BowType::Barebow => Class::barebow_classes(),
+    /// Current time of the server, which decides whether registrations are open
+    fn server_now(&self) -> NaiveDateTime {
+        Local::now().naive_local() + self.clock_offset.unwrap_or_else(chrono::Duration::zero)
+    }
+    /// The selected event, once the events are loaded
+    fn event(&self) -> Option<&EventInfo> {
+        self.events
+            .as_ref()?
+            .iter()
+            .find(|event| Some(event.id) == self.event_id)
+    }
+    /// Falls back to the first session if the selected one isn't offered by the event
+    fn update_session(&mut self) {
+        let Some(event) = self.event() else {
+            return;
+        };
+        if event.session(self.session).is_none() {
+            self.session = event.sessions.first().map_or(1, |session| session.id);
         }
-        .iter()
-        .filter(|cls| cls.in_range(self.date_of_birth))
-        .copied()
-        .collect();
+    }
+    /// Classes of the event matching the archer, none until the gender is chosen
+    fn available_classes(&self, event: &EventInfo) -> Vec<Class> {
+        match self.gender {
+            Some(gender) => event.classes_for(self.date_of_birth, self.bow_type, gender),
+            None => Vec::new(),
+        }
+    }
+    fn check_and_update_cls(&mut self, orders: &mut impl Orders<Msg>) {
+        let Some(event) = self.event() else {
+            return;
+        };
+        let available_classes = self.available_classes(event);
 
-        let new_cls = match (self.cls, available_classes.get(0)) {
-            (Some(cls), Some(&new)) => {
-                if available_classes.contains(&cls) {
+        let new_cls = match (&self.cls, available_classes.first()) {
+            (Some(cls), Some(new)) => {
+                if available_classes.contains(cls) {
                     return;
                 } else {
-                    Some(new)
+                    Some(new.clone())
                 }
             }
             (_, None) => None,
-            (None, Some(&new)) => Some(new),
+            (None, Some(new)) => Some(new.clone()),
         };
 
         self.update_target_face();
@@ -72,10 +126,9 @@ impl Model {
         orders.force_render_now();
     }
     fn update_target_face(&mut self) {
-        self.possible_target_faces = if let Some(cls) = self.cls {
-            TargetFace::for_cls(cls).to_owned()
-        } else {
-            Vec::new()
+        self.possible_target_faces = match (self.event(), &self.cls) {
+            (Some(event), Some(cls)) => event.target_faces_for(cls),
+            _ => Vec::new(),
         };
         if !self
             .possible_target_faces
@@ -119,6 +172,23 @@ fn init(url: Url, orders: &mut impl Orders<Msg>) -> Model {
     BASE_URL.with(|base_url| {
         *base_url.borrow_mut() = url.to_base_url();
     });
+    orders.perform_cmd(fetch_events());
+    orders.perform_cmd(fetch_class_rules());
+    orders.stream(streams::interval(1000, || Msg::Tick));
+    if let Some(token) = url.search().get("verify").and_then(|values| values.first()) {
+        orders.perform_cmd(post_verification(token.clone()));
+    }
+    if let Some(token) = url.search().get("manage").and_then(|values| values.first()) {
+        orders.perform_cmd(fetch_registration(token.clone()));
+        return Model {
+            manage: Some(Manage {
+                token: token.clone(),
+                loaded: false,
+                waiting_list: false,
+            }),
+            ..Model::new()
+        };
+    }
     let window = window();
     let Some(session_storage) = window.session_storage().ok().flatten() else {
         seed::log!("Couldn't load session storage");
@@ -146,19 +216,47 @@ enum Msg {
     FirstNameChanged(String),
     LastNameChanged(String),
     DateOfBirthChanged(String),
+    GenderChanged(Gender),
     MailChanged(String),
     BowTypeChange(BowType),
     ClassChanged(Option<Class>),
     TargetFaceChanged(TargetFace),
+    SessionChanged(i32),
     CommentChanged(String),
 
+    EventsLoaded(Vec<EventInfo>),
+    EventsLoadFailed(String),
+    ClassRulesLoaded(ClassRules),
+    ClassRulesLoadFailed(String),
+    EventChanged(i32),
+    /// Updates the countdown until the registration opens
+    Tick,
+
     Submit,
     RegistrationFailed(String),
+    /// The registration was saved and waits for the confirmation of the email address
     RegistrationOk,
+    Verified(Registration),
+    VerificationFailed(String),
+
+    RegistrationLoaded(Registration),
+    RegistrationLoadFailed(String),
+    RegistrationUpdated(Registration),
+    Withdraw,
+    Withdrawn,
 }
 
 fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
     match msg {
+        Msg::Tick => {
+            let window = model
+                .event()
+                .map(|event| event.registration_window(model.server_now()));
+            if !matches!(window, Some(RegistrationWindow::NotYetOpen(_))) {
+                orders.skip();
+            }
+            return;
+        }
         Msg::FirstNameChanged(n) => model.first_name = n,
         Msg::LastNameChanged(n) => model.last_name = n,
         Msg::DateOfBirthChanged(dob) => {
@@ -178,13 +276,18 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
                 InsertedMail::Invalid(mail)
             }
         }
+        Msg::GenderChanged(gender) => {
+            seed::log!("Selected gender", gender);
+            model.gender = Some(gender);
+            model.check_and_update_cls(orders);
+        }
         Msg::BowTypeChange(bt) => {
             seed::log!("Selected bow type", bt);
             model.bow_type = bt;
             model.check_and_update_cls(orders);
         }
         Msg::ClassChanged(cls) => {
-            seed::log!("Selected cls", cls.map(|cls| cls.name()));
+            seed::log!("Selected cls", cls.as_ref().map(|cls| cls.name()));
             model.cls = cls;
             model.update_target_face();
         }
@@ -192,44 +295,203 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             seed::log!("Selected target", tf);
             model.selected_target_face = tf;
         }
+        Msg::SessionChanged(session) => {
+            seed::log!("Selected session", session);
+            model.session = session;
+        }
+        Msg::EventsLoaded(events) => {
+            seed::log!("Loaded events", events.len());
+            if !events.iter().any(|event| Some(event.id) == model.event_id) {
+                model.event_id = events.first().map(|event| event.id);
+            }
+            model.clock_offset = events
+                .iter()
+                .find_map(|event| event.server_time)
+                .map(|server_time| server_time - Local::now().naive_local());
+            model.events = Some(events);
+            model.update_session();
+            model.check_and_update_cls(orders);
+            model.update_target_face();
+        }
+        Msg::EventsLoadFailed(err) => {
+            seed::error!("Loading events failed!", err);
+            seed::window()
+                .alert_with_message(&format!(
+                    "Veranstaltungen konnten nicht geladen werden! {err}"
+                ))
+                .ok();
+        }
+        Msg::ClassRulesLoaded(rules) => {
+            seed::log!("Loaded class rules", rules.classes.len());
+            ClassRules::set_current(rules);
+            model.check_and_update_cls(orders);
+            model.update_target_face();
+        }
+        Msg::ClassRulesLoadFailed(err) => {
+            // The built-in rules are mostly right, no reason to stop the registration
+            seed::error!("Loading class rules failed!", err);
+        }
+        Msg::EventChanged(id) => {
+            seed::log!("Selected event", id);
+            model.event_id = Some(id);
+            model.update_session();
+            model.check_and_update_cls(orders);
+            model.update_target_face();
+        }
         Msg::Submit => {
-            model.submitting = true;
-            orders.perform_cmd(post_participant(
-                common::archer::Archer::new(
-                    model.first_name.clone(),
-                    model.last_name.clone(),
-                    match &model.mail {
-                        InsertedMail::Invalid(_) => unreachable!(),
-                        InsertedMail::Valid(mail) => EmailAddress::from_str(mail).unwrap(),
-                    },
-                    model.date_of_birth,
-                    model.cls.expect("Submittion only possible if cls is set"),
-                    model.selected_target_face,
-                    model.comment.clone(),
-                )
-                .expect("It shouldn't be possible to produce invalid values"),
-            ));
+            let event = model
+                .event()
+                .expect("Form is only shown once the event is loaded");
+            let event_id = event.id;
+            match common::archer::Archer::new(
+                model.first_name.clone(),
+                model.last_name.clone(),
+                &model.mail.to_string(),
+                model.date_of_birth,
+                model
+                    .gender
+                    .expect("Submittion only possible if gender is set"),
+                model
+                    .cls
+                    .clone()
+                    .expect("Submittion only possible if cls is set"),
+                model.selected_target_face,
+                model.session,
+                model.comment.clone(),
+                event,
+            ) {
+                Ok(archer) => {
+                    model.submitting = true;
+                    match &model.manage {
+                        Some(manage) => {
+                            orders.perform_cmd(put_registration(manage.token.clone(), archer))
+                        }
+                        None => orders.perform_cmd(post_participant(event_id, archer)),
+                    };
+                }
+                Err(errors) => {
+                    orders.send_msg(Msg::RegistrationFailed(error_messages(&errors)));
+                }
+            }
         }
         Msg::RegistrationFailed(err) => {
             seed::window()
-                .alert_with_message(&format!("Fehler! {err:?}"))
+                .alert_with_message(&format!("Fehler! {err}"))
                 .ok();
             seed::error!("Submission failed!", err);
             model.submitting = false;
         }
         Msg::RegistrationOk => {
             seed::window()
-                .alert_with_message("Anmeldung erfolgreich. Bestätigungsmail wurde abgeschickt.")
+                .alert_with_message("Fast geschafft! Bitte bestätige deine Anmeldung über den Link in der Mail, die wir dir gerade geschickt haben.")
                 .ok();
             seed::log!("Submission ok!");
             *model = Model {
                 mail: model.mail.clone(),
+                event_id: model.event_id,
+                events: model.events.take(),
+                clock_offset: model.clock_offset,
+                session: model.session,
                 ..Model::new()
-            }
+            };
+            model.check_and_update_cls(orders);
+        }
+        Msg::Verified(registration) => {
+            seed::window()
+                .alert_with_message(if registration.waiting_list {
+                    "Danke für die Bestätigung! Die Veranstaltung ist leider ausgebucht. Du stehst auf der Warteliste und bekommst eine Mail, sobald ein Platz frei wird."
+                } else {
+                    "Danke für die Bestätigung! Deine Anmeldung ist jetzt gültig. Eine Bestätigungsmail wurde abgeschickt."
+                })
+                .ok();
+            BASE_URL.with(|base| base.borrow().clone().go_and_push());
+        }
+        Msg::VerificationFailed(err) => {
+            seed::error!("Verification failed!", err);
+            seed::window()
+                .alert_with_message(&format!("Bestätigung fehlgeschlagen! {err}"))
+                .ok();
+            BASE_URL.with(|base| base.borrow().clone().go_and_push());
         }
         Msg::CommentChanged(c) => model.comment = c,
+        Msg::RegistrationLoaded(registration) => {
+            seed::log!("Loaded registration");
+            model.first_name = registration.first_name;
+            model.last_name = registration.last_name;
+            model.mail = if EmailAddress::is_valid(&registration.mail) {
+                InsertedMail::Valid(registration.mail)
+            } else {
+                InsertedMail::Invalid(registration.mail)
+            };
+            if let Some(dob) = registration.date_of_birth {
+                model.date_of_birth = dob;
+            }
+            model.gender = registration.gender;
+            if let Some(cls) = &registration.class {
+                model.bow_type = cls.bow_type();
+            }
+            model.cls = registration.class;
+            if let Some(tf) = registration.target_face {
+                model.selected_target_face = tf;
+            }
+            model.session = registration.session;
+            model.comment = registration.comment;
+            model.event_id = Some(registration.event);
+            if let Some(manage) = &mut model.manage {
+                manage.loaded = true;
+                manage.waiting_list = registration.waiting_list;
+            }
+            model.update_session();
+            model.check_and_update_cls(orders);
+            model.update_target_face();
+        }
+        Msg::RegistrationLoadFailed(err) => {
+            seed::error!("Loading registration failed!", err);
+            seed::window()
+                .alert_with_message(&format!("Anmeldung konnte nicht geladen werden! {err}"))
+                .ok();
+            model.manage = None;
+            BASE_URL.with(|base| base.borrow().clone().go_and_push());
+        }
+        Msg::RegistrationUpdated(registration) => {
+            seed::window()
+                .alert_with_message(
+                    "Änderungen gespeichert. Eine neue Bestätigungsmail wurde abgeschickt.",
+                )
+                .ok();
+            model.submitting = false;
+            if let Some(manage) = &mut model.manage {
+                manage.waiting_list = registration.waiting_list;
+            }
+        }
+        Msg::Withdraw => {
+            let confirmed = seed::window()
+                .confirm_with_message("Willst du deine Anmeldung wirklich zurückziehen?")
+                .unwrap_or(false);
+            if let (true, Some(manage)) = (confirmed, &model.manage) {
+                model.submitting = true;
+                orders.perform_cmd(delete_registration(manage.token.clone()));
+            }
+        }
+        Msg::Withdrawn => {
+            seed::window()
+                .alert_with_message("Deine Anmeldung wurde zurückgezogen.")
+                .ok();
+            *model = Model {
+                event_id: model.event_id,
+                events: model.events.take(),
+                clock_offset: model.clock_offset,
+                ..Model::new()
+            };
+            model.check_and_update_cls(orders);
+            BASE_URL.with(|base| base.borrow().clone().go_and_push());
+        }
     }
 
+    // The form of a new registration must not be filled with the data of a managed one
+    if model.manage.is_some() {
+        return;
+    }
     if let Some(session_storage) = window().session_storage().ok().flatten() {
         session_storage
             .set_item("model", &serde_json::to_string(&model).unwrap())
@@ -238,10 +500,61 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
 }
 
 fn view(model: &Model) -> Node<Msg> {
-    let dob = model.date_of_birth;
-    let bow_type = model.bow_type;
+    let Some(events) = &model.events else {
+        return div![C!("main"), "Lade Veranstaltungen..."];
+    };
+    if let Some(Manage { loaded: false, .. }) = model.manage {
+        return div![C!("main"), "Lade Anmeldung..."];
+    }
+    let Some(event) = model.event() else {
+        return div![C!("main"), "Zur Zeit sind keine Anmeldungen möglich."];
+    };
+    let now = model.server_now();
+    let window = event.registration_window(now);
+    // Existing registrations can be looked at and withdrawn at any time
+    let events = match model.manage {
+        Some(_) => std::slice::from_ref(event),
+        None => events.as_slice(),
+    };
+    match window {
+        _ if model.manage.is_some() => {}
+        RegistrationWindow::NotYetOpen(opens) => {
+            return ul![
+                C!("main"),
+                event_header(events, event),
+                li!(format!(
+                    "Die Anmeldung öffnet am {}.",
+                    opens.format("%d.%m.%Y um %H:%M Uhr")
+                )),
+                li!(strong!(countdown(opens - now))),
+            ]
+        }
+        RegistrationWindow::Closed(_) => {
+            return ul![
+                C!("main"),
+                event_header(events, event),
+                li!("Die Anmeldung ist leider geschlossen."),
+            ]
+        }
+        RegistrationWindow::Open => {}
+    }
+    let available_classes = model.available_classes(event);
+    let selectable_classes = available_classes.clone();
+    let editable = window == RegistrationWindow::Open;
     ul![
         C!("main"),
+        event_header(events, event),
+        event.registration_closes.map(|closes| li!(em!(format!(
+            "Anmeldeschluss: {}",
+            closes.format("%d.%m.%Y um %H:%M Uhr")
+        )))),
+        model.manage.as_ref().map(|manage| vec![
+            li!(br!()),
+            li!(h3!("Deine Anmeldung")),
+            IF!(manage.waiting_list => li!(em!("Du stehst auf der Warteliste."))).unwrap_or_else(empty),
+            IF!(!editable => li!(em!("Die Anmeldung ist geschlossen, Änderungen sind nicht mehr möglich."))).unwrap_or_else(empty),
+        ]),
+        li!(br!()),
         li!("Vorname:"),
         li!(input!(
             attrs!(At::Value => model.first_name),
@@ -255,7 +568,9 @@ fn view(model: &Model) -> Node<Msg> {
         li!("Email Adresse:"),
         li!(
             input!(
-                attrs!(At::Value => model.mail, At::Type => "email", At::Style => format!("color: {}", if model.mail.is_valid(){"black"} else {"red"}))
+                attrs!(At::Value => model.mail, At::Type => "email", At::Style => format!("color: {}", if model.mail.is_valid(){"black"} else {"red"})),
+                // The address is confirmed, changing it needs a new registration
+                IF!(model.manage.is_some() => attrs!(At::ReadOnly => AtValue::None))
             ),
             input_ev(Ev::Input, Msg::MailChanged)
         ),
@@ -264,6 +579,21 @@ fn view(model: &Model) -> Node<Msg> {
             attrs!(At::Value => model.date_of_birth, At::Type => "date", ),
             input_ev(Ev::Input, Msg::DateOfBirthChanged)
         )),
+        li!("Geschlecht:"),
+        li!(
+            input!(
+                attrs!(At::Type => "radio", At::Name => "gender", At::Id => "male"),
+                IF!(model.gender == Some(Gender::Male) => attrs!("checked" => AtValue::None)),
+                input_ev(Ev::Input, |_| Msg::GenderChanged(Gender::Male))
+            ),
+            label!("männlich", attrs!(At::For => "male")),
+            input!(
+                attrs!(At::Type => "radio", At::Name => "gender", At::Id => "female"),
+                IF!(model.gender == Some(Gender::Female) => attrs!("checked" => AtValue::None)),
+                input_ev(Ev::Input, |_| Msg::GenderChanged(Gender::Female))
+            ),
+            label!("weiblich", attrs!(At::For => "female"))
+        ),
         li!(br!()),
         li!("Bogenart:"),
         li!(
@@ -309,32 +639,29 @@ fn view(model: &Model) -> Node<Msg> {
             attrs!(At::Name => "cls"),
             select!(
                 attrs!(At::Name => "Class",At::AutoComplete => "off", At::Required => AtValue::None),
-                model.cls.map(|cls| attrs!(At::Value => cls.name())),
-                match model.bow_type {
-                    BowType::Recurve => Class::recurve_classes(),
-                    BowType::Compound => Class::compound_classes(),
-                    BowType::Barebow => Class::barebow_classes(),
-                }
-                .iter()
-                .filter(|cls| cls.in_range(model.date_of_birth))
+                model.cls.as_ref().map(|cls| attrs!(At::Value => cls.name())),
+                available_classes
+                .into_iter()
                 .map(|cls| option!(
                     cls.name(),
                     attrs!(At::Value => cls.name()),
-                    IF!(Some(*cls) == model.cls => attrs!(At::Selected => AtValue::None)),
-                    ev(Ev::Input, |_| { Msg::ClassChanged(Some(*cls)) })
+                    IF!(model.cls.as_ref() == Some(&cls) => attrs!(At::Selected => AtValue::None)),
+                    ev(Ev::Input, move |_| { Msg::ClassChanged(Some(cls)) })
                 ))
                 .collect::<Vec<_>>(),
                 input_ev(Ev::Input, move |cls_name| {
                     Msg::ClassChanged(
-                        Some(Class::classes_for(dob, bow_type)
-                            .into_iter()
+                        Some(selectable_classes
+                            .iter()
                             .find(|cls| cls.name() == cls_name)
+                            .cloned()
                             .unwrap()),
                     )
                 })
             )
         ),
-        li!(em!(model.cls.map(|cls| cls.comment()))),
+        li!(em!(model.cls.as_ref().map(|cls| cls.comment()))),
+        IF!(model.gender.is_none() => li!(em!("Bitte wähle zuerst das Geschlecht, die Klassen hängen davon ab."))),
         li!(br!()),
         li!("Auflage:"),
         li!(
@@ -344,6 +671,17 @@ fn view(model: &Model) -> Node<Msg> {
             ]),
 
         ),
+        IF!(!event.sessions.is_empty() => vec![
+            li!(br!()),
+            li!("Durchgang:"),
+            li!(event.sessions.iter().map(|session| {
+                let id = session.id;
+                div![
+                    input!(attrs!(At::Type => "radio", At::Name => "session", At::Id => format!("session{id}")), IF!(model.session == id => attrs!(At::Checked => AtValue::None)), input_ev(Ev::Input, move |_| Msg::SessionChanged(id))),
+                    label!(format!("{} ({} Uhr)", session.name, session.start.format("%H:%M")), attrs!(At::For => format!("session{id}")))
+                ]
+            })),
+        ]),
         li!(br!()),
         li!("Kommentar:"),
         li!(textarea!(
@@ -352,15 +690,91 @@ fn view(model: &Model) -> Node<Msg> {
         )),
         li!(br!()),
         li!(button!(
-            "Anmelden",
-            IF!(model.first_name.is_empty() || model.last_name.is_empty() || !model.mail.is_valid() || model.cls.is_none() || model.submitting => attrs!(At::Disabled => AtValue::None)),
+            if model.manage.is_some() { "Änderungen speichern" } else { "Anmelden" },
+            IF!(model.first_name.is_empty() || model.last_name.is_empty() || !model.mail.is_valid() || model.gender.is_none() || model.cls.is_none() || model.submitting || !editable => attrs!(At::Disabled => AtValue::None)),
             input_ev(Ev::Click, |_| Msg::Submit)
-        ))
+        )),
+        IF!(model.manage.is_some() => li!(button!(
+            "Anmeldung zurückziehen",
+            IF!(model.submitting => attrs!(At::Disabled => AtValue::None)),
+            input_ev(Ev::Click, |_| Msg::Withdraw)
+        )))
+    ]
+}
+
+/// Event selection (if there is more than one event) and general information about the event
+fn event_header(events: &[EventInfo], event: &EventInfo) -> Vec<Node<Msg>> {
+    vec![
+        IF!(events.len() > 1 => li!(select!(
+            attrs!(At::Name => "event", At::AutoComplete => "off"),
+            events.iter().map(|e| option!(
+                &e.name,
+                attrs!(At::Value => e.id),
+                IF!(e.id == event.id => attrs!(At::Selected => AtValue::None)),
+            )),
+            input_ev(Ev::Input, |id| id.parse().map(Msg::EventChanged).ok())
+        )))
+        .unwrap_or_else(empty),
+        li!(h2!(&event.name)),
+        li!(format!(
+            "{} in {}",
+            event.date.format("%d.%m.%Y"),
+            event.venue
+        )),
     ]
 }
 
-async fn post_participant(archer: common::archer::Archer) -> Msg {
-    let url = BASE_URL.with(|base| base.borrow().clone().set_path(&["api", "archers"]));
+fn countdown(remaining: chrono::Duration) -> String {
+    let seconds = remaining.num_seconds();
+    let time = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    match seconds / 86400 {
+        0 => format!("Noch {time}"),
+        1 => format!("Noch 1 Tag und {time}"),
+        days => format!("Noch {days} Tage und {time}"),
+    }
+}
+
+async fn fetch_events() -> Msg {
+    let url = BASE_URL.with(|base| base.borrow().clone().set_path(["api", "events"]));
+    let response = match fetch(url.to_string()).await {
+        Ok(r) => r,
+        Err(e) => return Msg::EventsLoadFailed(format!("{e:?}")),
+    };
+    match response.check_status() {
+        Ok(response) => match response.json().await {
+            Ok(events) => Msg::EventsLoaded(events),
+            Err(e) => Msg::EventsLoadFailed(format!("{e:?}")),
+        },
+        Err(e) => Msg::EventsLoadFailed(format!("{e:?}")),
+    }
+}
+
+async fn fetch_class_rules() -> Msg {
+    let url = BASE_URL.with(|base| base.borrow().clone().set_path(["api", "classes"]));
+    let response = match fetch(url.to_string()).await {
+        Ok(r) => r,
+        Err(e) => return Msg::ClassRulesLoadFailed(format!("{e:?}")),
+    };
+    match response.check_status() {
+        Ok(response) => match response.json().await {
+            Ok(rules) => Msg::ClassRulesLoaded(rules),
+            Err(e) => Msg::ClassRulesLoadFailed(format!("{e:?}")),
+        },
+        Err(e) => Msg::ClassRulesLoadFailed(format!("{e:?}")),
+    }
+}
+
+async fn post_participant(event_id: i32, archer: common::archer::Archer) -> Msg {
+    let url = BASE_URL.with(|base| {
+        base.borrow()
+            .clone()
+            .set_path(["api", "events", &event_id.to_string(), "archers"])
+    });
     let request = Request::new(url.to_string())
         .method(Method::Post)
         .json(&archer)
@@ -372,13 +786,120 @@ async fn post_participant(archer: common::archer::Archer) -> Msg {
     let text = response.text().await;
     match response.check_status() {
         Ok(_) => Msg::RegistrationOk,
-        Err(e) => {
-            seed::log!(e);
-            Msg::RegistrationFailed(text.unwrap_or(format!("{e:?}")))
-        }
+        Err(e) => Msg::RegistrationFailed(failure_message(text, e)),
+    }
+}
+
+async fn post_verification(token: String) -> Msg {
+    let url = BASE_URL.with(|base| {
+        base.borrow()
+            .clone()
+            .set_path(["api", "verifications", &token])
+    });
+    let request = Request::new(url.to_string()).method(Method::Post);
+    let response = match fetch(request).await {
+        Ok(r) => r,
+        Err(e) => return Msg::VerificationFailed(format!("{e:?}")),
+    };
+    let text = response.text().await;
+    match response.check_status() {
+        Ok(_) => match text.map(|text| serde_json::from_str(&text)) {
+            Ok(Ok(registration)) => Msg::Verified(registration),
+            Ok(Err(e)) => Msg::VerificationFailed(format!("{e:?}")),
+            Err(e) => Msg::VerificationFailed(format!("{e:?}")),
+        },
+        Err(e) => Msg::VerificationFailed(text.unwrap_or(format!("{e:?}"))),
+    }
+}
+
+async fn fetch_registration(token: String) -> Msg {
+    let url = BASE_URL.with(|base| {
+        base.borrow()
+            .clone()
+            .set_path(["api", "registrations", &token])
+    });
+    let response = match fetch(url.to_string()).await {
+        Ok(r) => r,
+        Err(e) => return Msg::RegistrationLoadFailed(format!("{e:?}")),
+    };
+    let text = response.text().await;
+    match response.check_status() {
+        Ok(_) => match text.map(|text| serde_json::from_str(&text)) {
+            Ok(Ok(registration)) => Msg::RegistrationLoaded(registration),
+            Ok(Err(e)) => Msg::RegistrationLoadFailed(format!("{e:?}")),
+            Err(e) => Msg::RegistrationLoadFailed(format!("{e:?}")),
+        },
+        Err(e) => Msg::RegistrationLoadFailed(text.unwrap_or(format!("{e:?}"))),
+    }
+}
+
+async fn put_registration(token: String, archer: common::archer::Archer) -> Msg {
+    let url = BASE_URL.with(|base| {
+        base.borrow()
+            .clone()
+            .set_path(["api", "registrations", &token])
+    });
+    let request = Request::new(url.to_string())
+        .method(Method::Put)
+        .json(&archer)
+        .unwrap();
+    let response = match fetch(request).await {
+        Ok(r) => r,
+        Err(e) => return Msg::RegistrationFailed(format!("{e:?}")),
+    };
+    let text = response.text().await;
+    match response.check_status() {
+        Ok(_) => match text.map(|text| serde_json::from_str(&text)) {
+            Ok(Ok(registration)) => Msg::RegistrationUpdated(registration),
+            Ok(Err(e)) => Msg::RegistrationFailed(format!("{e:?}")),
+            Err(e) => Msg::RegistrationFailed(format!("{e:?}")),
+        },
+        Err(e) => Msg::RegistrationFailed(failure_message(text, e)),
     }
 }
 
+async fn delete_registration(token: String) -> Msg {
+    let url = BASE_URL.with(|base| {
+        base.borrow()
+            .clone()
+            .set_path(["api", "registrations", &token])
+    });
+    let request = Request::new(url.to_string()).method(Method::Delete);
+    let response = match fetch(request).await {
+        Ok(r) => r,
+        Err(e) => return Msg::RegistrationFailed(format!("{e:?}")),
+    };
+    let text = response.text().await;
+    match response.check_status() {
+        Ok(_) => Msg::Withdrawn,
+        Err(e) => Msg::RegistrationFailed(failure_message(text, e)),
+    }
+}
+
+/// Message for a failed request, with the single messages of a `422 Unprocessable Entity` response
+fn failure_message(text: seed::fetch::Result<String>, error: FetchError) -> String {
+    seed::log!(error);
+    let text = text.unwrap_or(format!("{error:?}"));
+    match serde_json::from_str::<ValidationErrors>(&text) {
+        Ok(ValidationErrors { errors }) => error_messages(&errors),
+        Err(_) => text,
+    }
+}
+
+/// Body of a `422 Unprocessable Entity` response
+#[derive(Deserialize)]
+struct ValidationErrors {
+    errors: Vec<RegistrationError>,
+}
+
+fn error_messages(errors: &[RegistrationError]) -> String {
+    errors
+        .iter()
+        .map(|e| e.message_de())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[wasm_bindgen(start)]
 pub fn start() {
     App::start("app", init, update, view);
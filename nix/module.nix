@@ -42,13 +42,38 @@ in
         This option is mandatory because you shouldn't put the real password into the nix store (settings of this module).
       '';
     };
+    link-secret-file = mkOption {
+      type = types.str;
+      example = "/etc/passwords/link.secret";
+      description = ''
+        Path to a file containing the key for signing the self-service links in the mails.
+        This option is mandatory because links signed with a known key could be forged.
+      '';
+    };
     settings = mkOption {
       type = pkgs.formats.toml.type;
       default = { };
       example = literalExpression ''
         {
           port = 3000;
+          public_url = "https://anmeldung.example.com";
+          verification_hours = 48;
+          events = [{
+            id = 1;
+            name = "Vereinsmeisterschaft WA";
+            date = "2023-05-21";
+            venue = "Bogensportplatz";
+            season_start = "2023-01-01";
+            capacity = 60;
+            registration_opens = "2023-04-01T00:00:00";
+            registration_closes = "2023-05-14T23:59:59";
+            sessions = [
+              { id = 1; name = "Vormittag"; start = "09:00:00"; max_butts = 12; }
+              { id = 2; name = "Nachmittag"; start = "14:00:00"; max_archers = 30; }
+            ];
+          }];
           mail_server = {
+            transport = "relay";
             smtp_server = "smtp.mymail.com";
             smtp_username = "myuser";
             smtp_password = "t0p_secret";
@@ -57,7 +82,14 @@ in
             sender_name = "Sender";
             sender_address = "me@mymail.com";
             subject = "Registration accepted";
+            organizer_addresses = [ "sportleitung@mymail.com" ];
+            organizer_mode = "digest";
           };
+          admins = [{
+            username = "admin";
+            password_hash_file = "/etc/passwords/admin.hash";
+            role = "editor";
+          }];
         }
       '';
       description = ''
@@ -72,8 +104,14 @@ in
         ${service-pkg}/bin/backend \
         --config-file ${pkgs.formats.toml.generate "${service-name} cfg.settings"} \
         --mail-template-file ${../backend/user_mail.tpl} \
+        --mail-html-template-file ${../backend/user_mail.html.tpl} \
+        --promotion-mail-template-file ${../backend/promotion_mail.tpl} \
+        --withdrawal-mail-template-file ${../backend/withdrawal_mail.tpl} \
+        --verification-mail-template-file ${../backend/verification_mail.tpl} \
+        --organizer-mail-template-file ${../backend/organizer_mail.tpl} \
         --database-file ${cfg.database-location} \
-        --mail-password-file ${cfg.smtp-password-file}
+        --mail-password-file ${cfg.smtp-password-file} \
+        --link-secret-file ${cfg.link-secret-file}
       '';
     };
 